@@ -4,80 +4,8 @@ const DEBUG_GAME_PAD = !true;
 
 window.viewConfig = {x: 0, y: 0.6, rotation: 0, zoom: 30.0};
 
-const style = [ '#04aaac', '#ac0204', '#acaaac', '#aa04ac', '#aaac04' ]
+// levels live in levels/ and are compiled into the wasm module, see `game.level_names()`
 const selectedLevel = 1;
-const inertia = 0.1;
-const levels = [
-    {
-    buildings: [
-        { x: -9.6, w: 5, h: 15, fill_style: style[2] },
-        { x: -7.3, w: 5, h: 30, fill_style: style[1] },
-        { x: -4.6, w: 7, h: 40, fill_style: style[4] },
-
-        { x: 4.6, w: 7, h: 40, fill_style: style[3] },
-        { x: 7.3, w: 5, h: 30, fill_style: style[0] },
-        { x: 9.6, w: 5, h: 15, fill_style: style[2] },
-    ],
-
-    player_a: { x: -7.3, y: -4.0, radx: 0.2, rady: 0.3, inertia },
-    player_b: { x: 7.3, y: -4.0, radx: 0.2, rady: 0.3, inertia },
-},
-    {
-    buildings: [
-        { x: -7.0, w: 7, h: 40, fill_style: style[0] },
-        { x: -4.3, w: 5, h: 25, fill_style: style[1] },
-        { x: -2.0, w: 5, h: 15, fill_style: style[2] },
-
-        { x: 2.0, w: 5, h: 15, fill_style: style[4] },
-        { x: 4.3, w: 5, h: 25, fill_style: style[0] },
-        { x: 7.0, w: 7, h: 40, fill_style: style[3] },
-    ],
-
-    player_a: { x: -7.0, y: -4.0, radx: 0.2, rady: 0.3, inertia },
-    player_b: { x: 7.0, y: -4.0, radx: 0.2, rady: 0.3, inertia },
-},
-{
-    buildings: [
-        { x: -7.0, w: 5, h: 21, fill_style: style[0] },
-        { x: -4.6, w: 3, h: 12, fill_style: style[1] },
-        { x: -3.0, w: 3, h: 15, fill_style: style[2] },
-
-        { x: 0.0, w: 7, h: 38, fill_style: style[3] },
-
-        { x: 3.0, w: 3, h: 15, fill_style: style[4] },
-        { x: 4.6, w: 3, h: 12, fill_style: style[1] },
-        { x: 7.0, w: 5, h: 21, fill_style: style[0] },
-    ],
-    player_a: { x: -7.0, y: -1.0, radx: 0.2, rady: 0.3, inertia },
-    player_b: { x: 7.0, y: -1.0, radx: 0.2, rady: 0.3, inertia },
-},
-(() => {
-
-    const leftBuildings = [
-        { x: -1.6, w: 5, h: 45, fill_style: style[2] },
-        { x: -9.6, w: 5, h: 15, fill_style: style[2] },
-        { x: -7.3, w: 5, h: 30, fill_style: style[1] },
-        { x: -4.6, w: 7, h: 40, fill_style: style[4] },
-    ];
-
-    const rightBuildings = leftBuildings.map(building => ({
-        ...building,
-        x: -building.x,
-    }));
-
-
-    return {
-        buildings: [
-            ...leftBuildings,
-            ... rightBuildings
-        ],
-
-        player_a: { x: -7.3, y: -4.0, radx: 0.2, rady: 0.3, inertia: 0.5 },
-        player_b: { x: 7.3, y: -4.0, radx: 0.2, rady: 0.3, inertia: 0.5 },
-    };
-})()
-];
-
 
 const shotConfigs = {
     light: {
@@ -151,52 +79,85 @@ void async function main() {
         max_position_iterations: 2,
     };
 
-    const game = new wasm.Game(canvas, gorilla_img, { width, height, integration_parameters });
+    let game;
+    try {
+        game = new wasm.Game(canvas, gorilla_img, { width, height, integration_parameters });
+    } catch (error) {
+        showError(error);
+        return;
+    }
     window.game = game;
+    // every throw's spin and every generated city follows from this seed
+    game.set_seed(BigInt(Date.now()));
 
 
-    const margin = 0.00000000001;
-    game.set_scene({
-        margin,
-        gravity: 9.81,
-        box_radx: 0.21 - margin,
-        box_rady: 0.10 - margin,
-        ground_radx: 125 - margin,
-        ground_rady: 4.5 ,
-        ground_x: 0,
-        ground_y: 9,
-        f1: 1,
-        f2: 2,
-
-        ... levels[selectedLevel % levels.length]
-    });
+    const levelNames = game.level_names();
+    tryOrShow(() => game.load_level(levelNames[selectedLevel % levelNames.length]));
+
+    const hud = document.createElement('div');
+    hud.style.textAlign = 'center';
+    hud.style.fontFamily = 'monospace';
+    document.body.appendChild(hud);
 
     let last_time = + performance.now();
+    // the computer plays player 2 until a second gamepad shows up
+    let computerPlays = false;
 
     const loop = (timestamp) => {
-        if (!window.paused) {
-            try { scangamepads(); } catch {}
+        // seconds, the game carries leftovers over to the next frame
+        const dt = (+timestamp - last_time) / 1000;
+        last_time = +timestamp;
+
+        try { scangamepads(); } catch {}
+
+        if (controllers[0]) controlPlayer(0, controllers[0], (btn) => tryOrShow(() => shoot({game, playerIndex: 0}, btn)));
+        if (controllers[1]) controlPlayer(1, controllers[1], (btn) => tryOrShow(() => shoot({game, playerIndex: 1}, btn)));
+        if (!controllers[1] && !computerPlays) {
+            const {light, medium, heavy} = shotConfigs;
+            tryOrShow(() => game.play_computer(1, { difficulty: 'Normal', weapons: [light, medium, heavy] }));
+            computerPlays = true;
+        } else if (controllers[1] && computerPlays) {
+            game.stop_computer(1);
+            computerPlays = false;
+        }
 
-            if (controllers[0]) controlPlayer(0, controllers[0], (btn) => shoot({game, playerIndex: 0}, btn));
-            if (controllers[1]) controlPlayer(1, controllers[1], (btn) => shoot({game, playerIndex: 1}, btn));
+        // does nothing while paused
+        game.step(dt);
+        for (const event of game.poll_events()) handleEvent(event);
+        tryOrShow(() => game.render_scene(viewConfig));
+        showStatus(hud, game.match_status(), game.wind());
 
-            const dt = +timestamp - last_time;
-            last_time = +timestamp;
+        requestAnimationFrame(loop);
+    }
 
-            game.step(1 / 60);
-            game.render_scene(viewConfig);
+    // the place to hook up sounds and effects
+    function handleEvent(event) {
+        switch (event.event) {
+            case 'GorillaDied':
+                console.info(`gorilla ${event.gorilla} is down`);
+                break;
+            case 'ShotRejectedCooldown':
+                console.debug(`gorilla ${event.gorilla} can throw again in ${event.remaining.toFixed(1)}s`);
+                break;
+            case 'ShotRejectedNotStarted':
+                console.debug(`the round starts in ${event.remaining.toFixed(1)}s`);
+                break;
+            case 'BrickDestroyed':
+            case 'Explosion':
+            case 'BananaHitGorilla':
+                console.debug(event);
+                break;
         }
-
-        requestAnimationFrame(loop);
     }
 
     handleKeyboard(({
         w,a,s,d,
         ArrowUp, ArrowLeft, ArrowDown, ArrowRight,
-        Pause
+        Pause, Enter
     }) => {
         switch (true) {
-            case Pause: window.paused = !window.paused ; break;
+            case Pause: game.toggle_pause(); break;
+            case Enter: game.start_match(); break;
             case w: game.move_gorilla(0, {x:  0,   y: -0.1}); break;
             case a: game.move_gorilla(0, {x: -0.1, y:  0  }); break;
             case s: game.move_gorilla(0, {x:  0,   y:  0.1}); break;
@@ -208,7 +169,7 @@ void async function main() {
             case ArrowRight: game.move_gorilla(1, {x:  0.1, y:  0  }); break;
         }
     });
-    loop();
+    requestAnimationFrame(loop);
 }()
 
 function controlPlayer(playerIndex, gamePad, shootCallback) {
@@ -263,18 +224,62 @@ function shoot({game, playerIndex}, {btnX, btnO, btnSqr, btnTri, rot, hori1, ver
     }
 
     if (btnX || btnO || btnTri || btnSqr) {
-        game.shoot(shot, (Math.random() - 0.5) * 100);
+        game.shoot(shot);
+    }
+}
+
+function showStatus(hud, { state, scores, round, rounds_to_win, turn }, wind) {
+    const score = `${scores[0]} : ${scores[1]}`;
+    const timer = turn && turn.remaining != null ? ` ${Math.ceil(turn.remaining)}s` : '';
+    const whose = turn ? `, player ${turn.player + 1}${turn.phase === 'Aiming' ? ' to throw' + timer : ''}` : '';
+    switch (state.state) {
+        case 'Lobby': hud.textContent = 'press Enter to start a match'; break;
+        case 'Countdown': hud.textContent = `round ${round} starts in ${Math.ceil(state.remaining)}  (${score})`; break;
+        case 'Playing': hud.textContent = `round ${round}, first to ${rounds_to_win}${whose}  (${score})`; break;
+        case 'RoundOver': hud.textContent = state.winner === null ? `draw  (${score})` : `player ${state.winner + 1} wins the round  (${score})`; break;
+        case 'MatchOver': hud.textContent = `player ${state.winner + 1} wins the match ${score}, press Enter for a rematch`; break;
+        case 'Paused': hud.textContent = `paused  (${score})`; break;
+    }
+    if (Math.abs(wind.x) > 0.01) {
+        hud.textContent += `  wind ${wind.x < 0 ? '←' : '→'} ${Math.abs(wind.x).toFixed(1)}`;
+    }
+}
+
+// the game rejects broken configs with `{ kind, context, level, field, expected, message }`
+function showError(error) {
+    console.error(error);
+    let box = document.getElementById('error');
+    if (!box) {
+        box = document.createElement('pre');
+        box.id = 'error';
+        box.style.color = 'red';
+        document.body.appendChild(box);
+    }
+    const { context, level, field, message } = error;
+    box.textContent = [
+        context,
+        level && `level "${level}"`,
+        field && `field ${field}`,
+        message || String(error),
+    ].filter(Boolean).join(': ');
+}
+
+function tryOrShow(fn) {
+    try {
+        return fn();
+    } catch (error) {
+        showError(error);
     }
 }
 
 function handleKeyboard(keyhandler) {
     const leftKeys = ['w', 'a', 's', 'd', ' '];
     const rightKeys = ['ArrowUp', 'ArrowLeft', 'ArrowDown', 'ArrowRight', '0'];
-    const knownKeys = [...leftKeys, ...rightKeys, 'Pause'];
+    const knownKeys = [...leftKeys, ...rightKeys, 'Pause', 'Enter'];
     document.addEventListener('keyup', ({key}) => {
         const gorillaKeys = {};
         knownKeys.forEach(exp => gorillaKeys[exp] = key === exp)
-        keyhandler(gorillaKeys)
+        tryOrShow(() => keyhandler(gorillaKeys))
     });
 
 }
\ No newline at end of file
@@ -0,0 +1,395 @@
+//! A computer player for when there is nobody to play against.
+//!
+//! It aims like a player who has done this a thousand times: a first guess from the throwing formula,
+//! then trial throws in a copy of the world, which gets gravity, wind and every building in the way right.
+//! Trials are kept cheap: a banana is followed for at most `TRIAL_HORIZON` seconds and given up on once it is past the target,
+//! only bricks near its path move, and one aim takes at most `MAX_TRIALS` of them, one per frame.
+//! The difficulty decides how far its hand shakes and how long it takes to make up its mind.
+
+use nalgebra::Vector2;
+use serde_derive::{Serialize, Deserialize};
+
+use std::f64::consts::PI;
+
+use crate::events::GameEvent;
+use crate::shapes::BananaConfig;
+use crate::simulation::Simulation;
+use crate::util::Rng;
+use crate::{GameConfig, GameError, Shot, Snapshot};
+
+/// the hardest a gamepad stick throws
+const MAX_POWER: f64 = 14.0;
+/// the softest throw worth trying
+const MIN_POWER: f64 = 4.0;
+/// the banana leaves the hand this far from the thrower's center, clear of the thrower
+const RELEASE_DISTANCE: f64 = 0.5;
+/// degrees above the horizon of the first trial throws, likeliest first so the trial budget cuts off the least promising
+const ELEVATIONS: [f64; 8] = [40.0, 50.0, 30.0, 60.0, 20.0, 70.0, 10.0, 80.0];
+/// rounds of trying small changes to the best throw so far
+const REFINE_ROUNDS: usize = 3;
+/// trial throws one aim takes at most
+pub const MAX_TRIALS: usize = 32;
+/// seconds a trial banana is followed at most, whatever its ttl
+pub const TRIAL_HORIZON: f64 = 2.0;
+/// trial throws per `think`, a frame can't wait for all of them
+const TRIALS_PER_THINK: usize = 1;
+/// a banana this far past or below the target is not going to hit it anymore
+const GIVE_UP_DISTANCE: f64 = 1.0;
+/// bricks farther than this from where the banana would fly in empty space don't move in trials
+const NEAR_PATH: f64 = 0.6;
+/// the frozen belt around them, and around each gorilla, that gives them something to stand on; bricks beyond are left out
+const SUPPORT: f64 = 0.5;
+/// points along that flight to measure the distance to
+const PATH_POINTS: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// radians the throw may be off, either way
+    fn aim_error(self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.12,
+            Difficulty::Normal => 0.04,
+            Difficulty::Hard => 0.0,
+        }
+    }
+
+    /// part of the power the throw may be off, either way
+    fn power_error(self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.15,
+            Difficulty::Normal => 0.05,
+            Difficulty::Hard => 0.0,
+        }
+    }
+
+    /// seconds between being allowed to throw and throwing
+    fn reaction(self) -> f64 {
+        match self {
+            Difficulty::Easy => 2.0,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 0.3,
+        }
+    }
+}
+
+/// Serializes like `{ difficulty: "Normal", weapons: [{ w, h, .. }], seed }`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AiConfig {
+    /// (default: `Normal`)
+    pub difficulty: Option<Difficulty>,
+    /// the bananas it may throw, it picks whichever does the most damage
+    pub weapons: Vec<BananaConfig>,
+    /// where its hand shakes, it has its own dice so the match doesn't change by having it (default: `0`)
+    pub seed: Option<u64>,
+}
+
+/// The throw the trials found best, before the hand shakes.
+#[derive(Debug, Clone)]
+pub struct Aim {
+    pub shot: Shot,
+    /// damage to the other gorillas minus damage to itself
+    pub damage: f64,
+    /// closest the banana got to the target
+    pub miss: f64,
+    /// trial throws it took to find
+    pub trials: usize,
+    /// physics ticks those trials ran
+    pub ticks: u64,
+}
+
+impl Aim {
+    fn is_better_than(&self, other: &Aim) -> bool {
+        self.damage > other.damage || (self.damage == other.damage && self.miss < other.miss)
+    }
+}
+
+/// Plays one gorilla: ask it every frame with `think`, throw what it returns.
+pub struct Ai {
+    gorilla: usize,
+    difficulty: Difficulty,
+    weapons: Vec<BananaConfig>,
+    rng: Rng,
+    /// seconds it has been allowed to throw without throwing
+    ready_for: f64,
+    /// the aim it is working on
+    search: Option<Search>,
+}
+
+impl Ai {
+    pub fn new(gorilla: usize, config: &AiConfig) -> Result<Self, GameError> {
+        if config.weapons.is_empty() {
+            return Err(GameError::config("Ai::new", "the computer needs at least one weapon"));
+        }
+        Ok(Ai {
+            gorilla,
+            difficulty: config.difficulty.unwrap_or(Difficulty::Normal),
+            weapons: config.weapons.clone(),
+            rng: Rng::new(config.seed.unwrap_or(0)),
+            ready_for: 0.0,
+            search: None,
+        })
+    }
+
+    pub fn gorilla(&self) -> usize {
+        self.gorilla
+    }
+
+    /// Called once per frame with the time since the last call,
+    /// returns a shot once it was allowed to throw for long enough to make up its mind.
+    ///
+    /// Aiming is spread over the frames it waits, `TRIALS_PER_THINK` trial throws each.
+    pub fn think(&mut self, sim: &Simulation, dt: f64) -> Option<Shot> {
+        if !self.may_throw(sim) {
+            self.ready_for = 0.0;
+            self.search = None;
+            return None;
+        }
+        self.ready_for += dt;
+        if self.search.is_none() {
+            self.search = self.start_search(sim);
+        }
+        let done = self.search.as_mut()?.run(TRIALS_PER_THINK);
+        if !done || self.ready_for < self.difficulty.reaction() {
+            return None;
+        }
+        self.ready_for = 0.0;
+        let aim = self.search.take()?.finish()?;
+        // it may have been pushed around while making up its mind
+        let from = sim.gorilla_pos(self.gorilla).ok()?;
+        let shot = release(self.gorilla, Vector2::new(from.x, from.y), aim.shot.rot, aim.shot.power, &aim.shot.config);
+        Some(self.shake(shot))
+    }
+
+    fn may_throw(&self, sim: &Simulation) -> bool {
+        let me = match sim.gorillas().get(self.gorilla) {
+            Some(me) => me,
+            None => return false,
+        };
+        me.is_alive() && me.time_to_next_shot <= 0.0 && sim.game_match().can_shoot(self.gorilla) && self.target(sim).is_some()
+    }
+
+    /// The closest other gorilla still standing.
+    fn target(&self, sim: &Simulation) -> Option<usize> {
+        let me = sim.gorilla_pos(self.gorilla).ok()?;
+        let distance = |index: usize| sim.gorilla_pos(index).map(|pos| (pos.x - me.x).hypot(pos.y - me.y)).unwrap_or(f64::INFINITY);
+        (0..sim.gorillas().len())
+            .filter(|&index| index != self.gorilla && sim.gorillas()[index].is_alive())
+            .min_by(|&a, &b| distance(a).partial_cmp(&distance(b)).unwrap_or(std::cmp::Ordering::Equal))
+    }
+
+    /// The best throw it can find right now, with a steady hand. `None` if there is nobody to throw at.
+    ///
+    /// All trials at once, `think` spreads the same search over several frames.
+    pub fn aim(&self, sim: &Simulation) -> Option<Aim> {
+        let mut search = self.start_search(sim)?;
+        search.run(MAX_TRIALS);
+        search.finish()
+    }
+
+    /// Lines up the first guesses, every weapon at every elevation.
+    fn start_search(&self, sim: &Simulation) -> Option<Search> {
+        let target = self.target(sim)?;
+        let from = sim.gorilla_pos(self.gorilla).ok()?;
+        let to = sim.gorilla_pos(target).ok()?;
+        let from = Vector2::new(from.x, from.y);
+        let to = Vector2::new(to.x, to.y);
+        // what pulls on a banana, as far as it is known now
+        let pull = sim.world().gravity() + sim.wind();
+
+        let mut todo = Vec::new();
+        for elevation in ELEVATIONS.iter() {
+            let rot = if to.x >= from.x { -elevation.to_radians() } else { PI + elevation.to_radians() };
+            let release_at = from + Vector2::new(rot.cos(), rot.sin()) * RELEASE_DISTANCE;
+            let power = match ballistic_power(to - release_at, rot, pull) {
+                Some(power) => power.clamp(MIN_POWER, MAX_POWER),
+                None => continue,
+            };
+            for weapon in &self.weapons {
+                todo.push(release(self.gorilla, from, rot, power, weapon));
+            }
+        }
+        todo.reverse();
+
+        Some(Search {
+            config: sim.replay().config.clone(),
+            snapshot: sim.snapshot(),
+            gorilla: self.gorilla,
+            target,
+            from,
+            pull,
+            todo,
+            best: None,
+            step: (0.04, 0.05),
+            rounds: 0,
+            trials: 0,
+            ticks: 0,
+        })
+    }
+
+    /// The shot as thrown by a hand as steady as the difficulty allows.
+    fn shake(&mut self, mut shot: Shot) -> Shot {
+        let aim_error = self.difficulty.aim_error();
+        let power_error = self.difficulty.power_error();
+        shot.rot += self.rng.range_f64(-aim_error, aim_error);
+        shot.power *= 1.0 + self.rng.range_f64(-power_error, power_error);
+        shot
+    }
+}
+
+/// An aim in the making, trial throws in copies of the world as it was when it started.
+struct Search {
+    config: GameConfig,
+    snapshot: Snapshot,
+    gorilla: usize,
+    target: usize,
+    from: Vector2<f64>,
+    pull: Vector2<f64>,
+    /// throws still to try, the next one last
+    todo: Vec<Shot>,
+    best: Option<Aim>,
+    /// how far the next refining round turns and pushes the best throw
+    step: (f64, f64),
+    rounds: usize,
+    trials: usize,
+    ticks: u64,
+}
+
+impl Search {
+    /// Tries up to `count` more throws, true once there is nothing left to try.
+    fn run(&mut self, count: usize) -> bool {
+        for _ in 0..count {
+            if self.trials == MAX_TRIALS {
+                return true;
+            }
+            let shot = match self.todo.pop() {
+                Some(shot) => shot,
+                None if self.refine() => continue,
+                None => return true,
+            };
+            self.trials += 1;
+            if let Some(aim) = self.trial(shot) {
+                if self.best.as_ref().is_none_or(|best| aim.is_better_than(best)) {
+                    self.best = Some(aim);
+                }
+            }
+        }
+        self.trials == MAX_TRIALS || (self.todo.is_empty() && !self.can_refine())
+    }
+
+    fn can_refine(&self) -> bool {
+        // close in on the best throw, unless it already hits
+        self.rounds < REFINE_ROUNDS && self.best.as_ref().is_some_and(|best| best.damage <= 0.0)
+    }
+
+    /// Lines up small changes to the best throw so far, false if it is done refining.
+    fn refine(&mut self) -> bool {
+        if !self.can_refine() {
+            return false;
+        }
+        let best = match &self.best {
+            Some(best) => best.shot.clone(),
+            None => return false,
+        };
+        let (rot, power, step) = (best.rot, best.power, self.step);
+        let tries = [(rot, power * (1.0 + step.1)), (rot, power * (1.0 - step.1)), (rot + step.0, power), (rot - step.0, power)];
+        for &(rot, power) in tries.iter().rev() {
+            self.todo.push(release(self.gorilla, self.from, rot, power.clamp(MIN_POWER, MAX_POWER), &best.config));
+        }
+        self.rounds += 1;
+        self.step = (step.0 * 0.5, step.1 * 0.5);
+        true
+    }
+
+    fn finish(self) -> Option<Aim> {
+        let (trials, ticks) = (self.trials, self.ticks);
+        self.best.map(|best| Aim { trials, ticks, ..best })
+    }
+
+    /// Throws `shot` in a copy of the world and follows the banana until it is gone, past the target or out of time.
+    fn trial(&mut self, shot: Shot) -> Option<Aim> {
+        // where it would fly in empty space, only bricks near that move, the gorillas keep what they stand on
+        let start = Vector2::new(shot.x, shot.y);
+        let velocity = Vector2::new(shot.rot.cos(), shot.rot.sin()) * shot.power;
+        let path: Vec<Vector2<f64>> = (0..=PATH_POINTS)
+            .map(|i| TRIAL_HORIZON * i as f64 / PATH_POINTS as f64)
+            .map(|t| start + velocity * t + self.pull * (t * t * 0.5))
+            .collect();
+        let gorillas: Vec<Vector2<f64>> = self.snapshot.gorillas.items.iter().map(|gorilla| Vector2::new(gorilla.body.x, gorilla.body.y)).collect();
+        let distance = |points: &[Vector2<f64>], x: f64, y: f64| points.iter().map(|point| (point.x - x).hypot(point.y - y)).fold(f64::INFINITY, f64::min);
+        let keep = |x: f64, y: f64| distance(&path, x, y) < NEAR_PATH + SUPPORT || distance(&gorillas, x, y) < SUPPORT;
+
+        let mut snapshot = self.snapshot.clone();
+        snapshot.bricks.items.retain(|brick| keep(brick.body.x, brick.body.y));
+        snapshot.debris.items.retain(|fragment| keep(fragment.body.x, fragment.body.y));
+        let mut copy = Simulation::scratch(&self.config, &snapshot).ok()?;
+        copy.freeze(|pos| distance(&path, pos.x, pos.y) < NEAR_PATH);
+
+        copy.shoot(&shot);
+        let banana = copy.poll_events().into_iter().find_map(|event| match event {
+            GameEvent::ShotFired { banana, .. } => Some(banana),
+            _ => None,
+        })?;
+        let ahead = if copy.gorilla_pos(self.target).ok()?.x >= start.x { 1.0 } else { -1.0 };
+
+        let mut aim = Aim { shot, damage: 0.0, miss: f64::INFINITY, trials: 1, ticks: 0 };
+        let ts = copy.world().timestep();
+        let ticks = (aim.shot.config.ttl.min(TRIAL_HORIZON) / ts).ceil() as u64 + 1;
+        let mut last = start;
+        for _ in 0..ticks {
+            copy.run_ticks(1);
+            aim.ticks += 1;
+            for event in copy.poll_events() {
+                if let GameEvent::BananaHitGorilla { gorilla, damage, by, .. } = event {
+                    if by != Some(self.gorilla) {
+                        continue;
+                    }
+                    aim.damage += if gorilla == self.gorilla { -damage } else { damage };
+                }
+            }
+            let body = match copy.entities().bananas.get(banana) {
+                Some(flying) => flying.body,
+                None => break,
+            };
+            let to = match copy.gorilla_pos(self.target) {
+                Ok(to) => to,
+                Err(_) => break,
+            };
+            let pos = copy.pos_of(body);
+            aim.miss = aim.miss.min((pos.x - to.x).hypot(pos.y - to.y));
+            let past = (pos.x - to.x) * ahead > GIVE_UP_DISTANCE;
+            let below = pos.y - to.y > GIVE_UP_DISTANCE && pos.y > last.y;
+            if past || below {
+                break;
+            }
+            last = pos;
+        }
+        self.ticks += aim.ticks;
+        Some(aim)
+    }
+}
+
+/// `rot` and `power` thrown by `gorilla` standing at `from`.
+fn release(gorilla: usize, from: Vector2<f64>, rot: f64, power: f64, weapon: &BananaConfig) -> Shot {
+    let at = from + Vector2::new(rot.cos(), rot.sin()) * RELEASE_DISTANCE;
+    Shot { x: at.x, y: at.y, rot, power, gorilla_id: gorilla, config: weapon.clone() }
+}
+
+/// Power to throw at angle `rot` to get `offset` away under the constant acceleration `pull`, air has no say.
+/// `None` if that angle doesn't get there at any power.
+pub fn ballistic_power(offset: Vector2<f64>, rot: f64, pull: Vector2<f64>) -> Option<f64> {
+    let (cos, sin) = (rot.cos(), rot.sin());
+    // offset = v t + pull t² / 2 along and across the throw, with u = t² / 2
+    let u = (offset.x * sin - offset.y * cos) / (pull.x * sin - pull.y * cos);
+    if !u.is_finite() || u <= 0.0 {
+        return None;
+    }
+    let t = (2.0 * u).sqrt();
+    let power = if cos.abs() > sin.abs() { (offset.x - pull.x * u) / (cos * t) } else { (offset.y - pull.y * u) / (sin * t) };
+    if power.is_finite() && power > 0.0 { Some(power) } else { None }
+}
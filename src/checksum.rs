@@ -0,0 +1,82 @@
+//! Cheap, stable hashes of the simulation state, to notice when two simulations that should agree don't.
+
+use serde_derive::{Serialize, Deserialize};
+
+use crate::entities::EntityId;
+
+/// 32 bit FNV-1a, the same on every platform and in every run, unlike the hasher of `std`.
+///
+/// 32 bits fit into a JS number, that is plenty to spot a divergence that lasts for more than a frame.
+#[derive(Debug, Clone, Copy)]
+pub struct Fnv(u32);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Fnv(0x811c_9dc5)
+    }
+}
+
+impl Fnv {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u32::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0193);
+        }
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    /// Bit for bit, `0.0` and `-0.0` differ.
+    pub fn write_f64(&mut self, value: f64) {
+        self.write_u64(value.to_bits());
+    }
+
+    pub fn write_opt_f64(&mut self, value: Option<f64>) {
+        match value {
+            Some(value) => {
+                self.write(&[1]);
+                self.write_f64(value);
+            }
+            None => self.write(&[0]),
+        }
+    }
+
+    pub fn write_opt_usize(&mut self, value: Option<usize>) {
+        match value {
+            Some(value) => {
+                self.write(&[1]);
+                self.write_u64(value as u64);
+            }
+            None => self.write(&[0]),
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Checksum of every entity after `tick`, in the order of `Simulation::entity_checksums`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntityChecksums {
+    pub tick: u64,
+    pub entities: Vec<u32>,
+}
+
+/// Where a replay stopped matching its recorded checksums.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Divergence {
+    /// the first tick whose checksum differs
+    pub tick: u64,
+    pub expected: u32,
+    pub actual: u32,
+    /// the first entity that differs at the first per entity checksums from `tick` on,
+    /// `None` if there are none or only what is not an entity differs, e.g. the match
+    pub entity: Option<EntityId>,
+}
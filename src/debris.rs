@@ -0,0 +1,103 @@
+use nalgebra::Vector2;
+use ncollide2d::shape::Cuboid;
+use ncollide2d::world::CollisionObjectHandle;
+use nphysics2d::object::{BodyHandle, Material};
+use nphysics2d::volumetric::Volumetric;
+use serde_derive::{Serialize, Deserialize};
+
+use crate::shapes::Brick;
+
+type World = nphysics2d::world::World<f64>;
+type Isometry2 = nalgebra::Isometry2<f64>;
+type ShapeHandle = ncollide2d::shape::ShapeHandle<f64>;
+
+/// What broken bricks leave behind, part of the `SceneConfig`. Without it they just vanish.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DebrisConfig {
+    /// a brick breaks into `split` × `split` fragments, `0` leaves nothing behind (default: `2`, at most `8`)
+    pub split: Option<usize>,
+    /// seconds a fragment lasts (default: `2`)
+    pub ttl: Option<f64>,
+    /// most fragments alive at once, the oldest make room for new ones (default: `64`, at most `1000`)
+    pub budget: Option<usize>,
+}
+
+impl DebrisConfig {
+    pub fn split(&self) -> usize {
+        self.split.unwrap_or(2)
+    }
+
+    pub fn ttl(&self) -> f64 {
+        self.ttl.unwrap_or(2.0)
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget.unwrap_or(64)
+    }
+}
+
+/// A piece of a broken brick, purely cosmetic: it collides but never takes or deals damage.
+pub struct Fragment {
+    pub shape: ShapeHandle,
+    pub body: BodyHandle,
+    pub collision_object: CollisionObjectHandle,
+    pub ttl: f64,
+    /// seconds it was spawned with, for fading out
+    pub max_ttl: f64,
+    pub fill_style: String,
+}
+
+/// Cuts `brick` into a grid of fragments moving along with it, call before removing the brick.
+/// Only the last `limit` fragments of the grid are spawned.
+///
+/// Fragments are a little smaller than their cell so they don't start out touching each other.
+pub fn fracture(world: &mut World, brick: &Brick, config: &DebrisConfig, margin: f64, limit: usize) -> Vec<Fragment> {
+    let split = config.split();
+    let first = (split * split).saturating_sub(limit);
+    let half_extents = match brick.shape.as_shape::<Cuboid<f64>>() {
+        Some(cuboid) => *cuboid.half_extents(),
+        None => return Vec::new(),
+    };
+    let (position, velocity) = match world.rigid_body(brick.body) {
+        Some(rb) => (rb.position(), *rb.velocity()),
+        None => return Vec::new(),
+    };
+    if split == 0 || limit == 0 {
+        return Vec::new();
+    }
+
+    let cell = half_extents / split as f64;
+    let shape = ShapeHandle::new(Cuboid::new(cell * 0.9));
+    let mut fragments = Vec::with_capacity(split * split - first);
+    for xi in 0..split {
+        for yi in 0..split {
+            if xi * split + yi < first {
+                continue;
+            }
+            let local = Vector2::new(
+                -half_extents.x + cell.x * (2 * xi + 1) as f64,
+                -half_extents.y + cell.y * (2 * yi + 1) as f64,
+            );
+            let offset = position.rotation * local;
+            let pos = Isometry2::from_parts((position.translation.vector + offset).into(), position.rotation);
+
+            let body = world.add_rigid_body(pos, shape.inertia(0.1), shape.center_of_mass());
+            let collision_object = world.add_collider(margin, shape.clone(), body, Isometry2::identity(), Material::new(0.0, 1.0));
+            if let Some(rb) = world.rigid_body_mut(body) {
+                // a spinning brick flings its outer pieces faster
+                let spin = Vector2::new(-offset.y, offset.x) * velocity.angular;
+                rb.set_linear_velocity(velocity.linear + spin);
+                rb.set_angular_velocity(velocity.angular);
+            }
+            fragments.push(Fragment {
+                shape: shape.clone(),
+                body,
+                collision_object,
+                ttl: config.ttl(),
+                max_ttl: config.ttl(),
+                fill_style: brick.fill_style.clone(),
+            });
+        }
+    }
+    fragments
+}
@@ -0,0 +1,351 @@
+//! Storage for everything in the scene, with constant time lookup by id and by collider.
+//!
+//! Colliders hand out their handles again once removed, so a handle alone can't tell
+//! a dead brick from the fragment that took its slot. Entity ids carry a generation instead,
+//! an id of something that is gone never resolves to whatever lives there now.
+
+use ncollide2d::world::CollisionObjectHandle;
+use nphysics2d::object::BodyHandle;
+use serde_derive::{Serialize, Deserialize};
+
+use std::collections::{HashMap, HashSet};
+
+use crate::debris::Fragment;
+use crate::shapes::{Banana, Brick, Gorilla};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Kind {
+    Ground,
+    Gorilla,
+    Brick,
+    Banana,
+    Debris,
+}
+
+/// Serializes to `{ kind, index, generation }`, two ids are the same entity if all three match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EntityId {
+    pub kind: Kind,
+    pub index: u32,
+    pub generation: u32,
+}
+
+/// Anything with a body and a collider in the physics world.
+pub trait Physical {
+    fn collision_object(&self) -> CollisionObjectHandle;
+    fn body(&self) -> BodyHandle;
+}
+
+impl Physical for Gorilla {
+    fn collision_object(&self) -> CollisionObjectHandle {
+        self.collision_object
+    }
+
+    fn body(&self) -> BodyHandle {
+        self.body
+    }
+}
+
+impl Physical for Brick {
+    fn collision_object(&self) -> CollisionObjectHandle {
+        self.collision_object
+    }
+
+    fn body(&self) -> BodyHandle {
+        self.body
+    }
+}
+
+impl Physical for Banana {
+    fn collision_object(&self) -> CollisionObjectHandle {
+        self.collision_object
+    }
+
+    fn body(&self) -> BodyHandle {
+        self.body
+    }
+}
+
+impl Physical for Fragment {
+    fn collision_object(&self) -> CollisionObjectHandle {
+        self.collision_object
+    }
+
+    fn body(&self) -> BodyHandle {
+        self.body
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Slot {
+    generation: u32,
+    /// where the entity sits in `items`, `None` while the slot is free
+    item: Option<usize>,
+}
+
+/// Which ids a `Store` has handed out, so a restored store goes on handing out the same ones as the original.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Slots {
+    /// current generation of every slot
+    pub generations: Vec<u32>,
+    /// slots up for reuse, the last one goes first
+    pub free: Vec<u32>,
+}
+
+/// Entities of one kind, kept in insertion order so they can be handed out as a slice.
+pub struct Store<T> {
+    kind: Kind,
+    items: Vec<T>,
+    /// the id of each item, in the same order
+    ids: Vec<EntityId>,
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+}
+
+impl<T> Store<T> {
+    pub fn new(kind: Kind) -> Self {
+        Store { kind, items: Vec::new(), ids: Vec::new(), slots: Vec::new(), free: Vec::new() }
+    }
+
+    /// A store holding `items` under the ids they had in the store `slots` were taken from,
+    /// `None` if the ids don't fit the slots.
+    pub fn restore(kind: Kind, slots: Slots, items: Vec<(EntityId, T)>) -> Option<Self> {
+        let mut store = Store::new(kind);
+        store.slots = slots.generations.into_iter().map(|generation| Slot { generation, item: None }).collect();
+        for (id, item) in items {
+            let slot = store.slots.get_mut(id.index as usize)
+                .filter(|slot| id.kind == kind && slot.generation == id.generation && slot.item.is_none())?;
+            slot.item = Some(store.items.len());
+            store.items.push(item);
+            store.ids.push(id);
+        }
+        let free_slot = |index: &u32| store.slots.get(*index as usize).is_some_and(|slot| slot.item.is_none());
+        if !slots.free.iter().all(free_slot) {
+            return None;
+        }
+        store.free = slots.free;
+        Some(store)
+    }
+
+    pub fn slots(&self) -> Slots {
+        Slots {
+            generations: self.slots.iter().map(|slot| slot.generation).collect(),
+            free: self.free.clone(),
+        }
+    }
+
+    pub fn insert(&mut self, item: T) -> EntityId {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot { generation: 0, item: None });
+                (self.slots.len() - 1) as u32
+            }
+        };
+        let slot = &mut self.slots[index as usize];
+        slot.item = Some(self.items.len());
+        let id = EntityId { kind: self.kind, index, generation: slot.generation };
+        self.items.push(item);
+        self.ids.push(id);
+        id
+    }
+
+    /// Position of `id` in `items()`, `None` if it is gone or was never here.
+    pub fn position(&self, id: EntityId) -> Option<usize> {
+        if id.kind != self.kind {
+            return None;
+        }
+        self.slots.get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.item)
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&T> {
+        self.position(id).map(|i| &self.items[i])
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut T> {
+        match self.position(id) {
+            Some(i) => Some(&mut self.items[i]),
+            None => None,
+        }
+    }
+
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    pub fn items_mut(&mut self) -> &mut [T] {
+        &mut self.items
+    }
+
+    pub fn ids(&self) -> &[EntityId] {
+        &self.ids
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &T)> {
+        self.ids.iter().cloned().zip(self.items.iter())
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Takes out every entity `remove` returns `true` for, the rest keep their order.
+    pub fn remove_where<F>(&mut self, mut remove: F) -> Vec<(EntityId, T)>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let mut removed = Vec::new();
+        let mut kept = Vec::with_capacity(self.items.len());
+        let mut kept_ids = Vec::with_capacity(self.ids.len());
+        for (id, item) in self.ids.drain(..).zip(self.items.drain(..)) {
+            if remove(&item) {
+                removed.push((id, item));
+            } else {
+                kept.push(item);
+                kept_ids.push(id);
+            }
+        }
+        self.items = kept;
+        self.ids = kept_ids;
+
+        for &(id, _) in &removed {
+            let slot = &mut self.slots[id.index as usize];
+            slot.generation = slot.generation.wrapping_add(1);
+            slot.item = None;
+            self.free.push(id.index);
+        }
+        for (i, id) in self.ids.iter().enumerate() {
+            self.slots[id.index as usize].item = Some(i);
+        }
+        removed
+    }
+
+    /// Takes out the `count` oldest entities.
+    pub fn remove_oldest(&mut self, count: usize) -> Vec<(EntityId, T)> {
+        let mut seen = 0;
+        self.remove_where(|_| {
+            seen += 1;
+            seen <= count
+        })
+    }
+}
+
+/// Everything in the scene, plus which entity each collider belongs to.
+pub struct Entities {
+    pub gorillas: Store<Gorilla>,
+    pub bricks: Store<Brick>,
+    pub bananas: Store<Banana>,
+    /// oldest first
+    pub debris: Store<Fragment>,
+    colliders: HashMap<CollisionObjectHandle, EntityId>,
+    /// every body handed out since the world was built, never forgotten: the world reuses the handles
+    /// of removed bodies, so this stays as big as the most bodies there were at once
+    bodies: HashSet<BodyHandle>,
+}
+
+impl Default for Entities {
+    fn default() -> Self {
+        Entities {
+            gorillas: Store::new(Kind::Gorilla),
+            bricks: Store::new(Kind::Brick),
+            bananas: Store::new(Kind::Banana),
+            debris: Store::new(Kind::Debris),
+            colliders: HashMap::new(),
+            bodies: HashSet::new(),
+        }
+    }
+}
+
+impl Entities {
+    /// Puts restored stores back together, `ground` is the collider of the ground if there is one.
+    pub fn from_stores(
+        ground: Option<CollisionObjectHandle>,
+        gorillas: Store<Gorilla>,
+        bricks: Store<Brick>,
+        bananas: Store<Banana>,
+        debris: Store<Fragment>,
+    ) -> Self {
+        let mut colliders = HashMap::new();
+        colliders.extend(gorillas.iter().map(|(id, gorilla)| (gorilla.collision_object(), id)));
+        colliders.extend(bricks.iter().map(|(id, brick)| (brick.collision_object(), id)));
+        colliders.extend(bananas.iter().map(|(id, banana)| (banana.collision_object(), id)));
+        colliders.extend(debris.iter().map(|(id, fragment)| (fragment.collision_object(), id)));
+        let mut bodies = HashSet::new();
+        bodies.extend(gorillas.items().iter().map(Physical::body));
+        bodies.extend(bricks.items().iter().map(Physical::body));
+        bodies.extend(bananas.items().iter().map(Physical::body));
+        bodies.extend(debris.items().iter().map(Physical::body));
+        let mut entities = Entities { gorillas, bricks, bananas, debris, colliders, bodies };
+        if let Some(ground) = ground {
+            entities.add_ground(ground);
+        }
+        entities
+    }
+
+    /// The ground is static and there is only one, it gets an id but no storage.
+    pub fn add_ground(&mut self, collider: CollisionObjectHandle) -> EntityId {
+        let id = EntityId { kind: Kind::Ground, index: 0, generation: 0 };
+        self.colliders.insert(collider, id);
+        id
+    }
+
+    pub fn add_gorilla(&mut self, gorilla: Gorilla) -> EntityId {
+        let (collider, body) = (gorilla.collision_object, gorilla.body);
+        let id = self.gorillas.insert(gorilla);
+        self.colliders.insert(collider, id);
+        self.bodies.insert(body);
+        id
+    }
+
+    pub fn add_brick(&mut self, brick: Brick) -> EntityId {
+        let (collider, body) = (brick.collision_object, brick.body);
+        let id = self.bricks.insert(brick);
+        self.colliders.insert(collider, id);
+        self.bodies.insert(body);
+        id
+    }
+
+    pub fn add_banana(&mut self, banana: Banana) -> EntityId {
+        let (collider, body) = (banana.collision_object, banana.body);
+        let id = self.bananas.insert(banana);
+        self.colliders.insert(collider, id);
+        self.bodies.insert(body);
+        id
+    }
+
+    pub fn add_fragment(&mut self, fragment: Fragment) -> EntityId {
+        let (collider, body) = (fragment.collision_object, fragment.body);
+        let id = self.debris.insert(fragment);
+        self.colliders.insert(collider, id);
+        self.bodies.insert(body);
+        id
+    }
+
+    /// The entity `collider` belongs to.
+    pub fn id_of(&self, collider: CollisionObjectHandle) -> Option<EntityId> {
+        self.colliders.get(&collider).cloned()
+    }
+
+    /// How many colliders are mapped to entities, the ground included.
+    pub fn tracked_colliders(&self) -> usize {
+        self.colliders.len()
+    }
+
+    /// Bodies that were ever in this world, removed ones included, to look up which of them still are.
+    pub fn spawned_bodies(&self) -> impl Iterator<Item = BodyHandle> + '_ {
+        self.bodies.iter().cloned()
+    }
+
+    /// Drops the collider mappings of entities taken out of their store, before their handles get reused.
+    pub fn forget<'a, T: Physical + 'a>(&mut self, removed: impl IntoIterator<Item = &'a (EntityId, T)>) {
+        for (_, entity) in removed {
+            self.colliders.remove(&entity.collision_object());
+        }
+    }
+}
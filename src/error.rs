@@ -0,0 +1,132 @@
+use serde::de::DeserializeOwned;
+use serde_derive::Serialize;
+use serde_json::Value;
+use serde_path_to_error::Path;
+
+use std::fmt;
+
+use crate::validation::SceneProblem;
+
+/// Everything the game refuses to do instead of panicking.
+///
+/// Serializes to a plain object (`{ kind: "Config", context, field, ... }`) for the JS side.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind")]
+pub enum GameError {
+    /// a config object did not have the expected shape
+    Config(ConfigError),
+    /// there is no gorilla with that index in the current scene
+    NoSuchGorilla { index: usize, count: usize },
+    /// the scene parsed fine but would build a broken world
+    InvalidScene { level: Option<String>, problems: Vec<SceneProblem> },
+    /// the level registry has no level of that name
+    NoSuchLevel { name: String, known: Vec<String> },
+    /// the other side of a networked match doesn't play along
+    Network { message: String },
+    /// a result could not be turned into a plain value for the JS side
+    Serialize { context: String, message: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigError {
+    /// the entry point that was handed the config, e.g. `set_scene`
+    pub context: String,
+    /// `name` of the scene, if there is one
+    pub level: Option<String>,
+    /// path to the offending field, e.g. `buildings[2].w`
+    pub field: Option<String>,
+    /// what we expected to find there, e.g. `usize`
+    pub expected: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameError::Config(err) => {
+                write!(f, "{}", err.context)?;
+                if let Some(ref level) = err.level {
+                    write!(f, " (level {:?})", level)?;
+                }
+                if let Some(ref field) = err.field {
+                    write!(f, " at {}", field)?;
+                }
+                write!(f, ": {}", err.message)
+            }
+            GameError::NoSuchGorilla { index, count } => {
+                write!(f, "there is no gorilla {}, only {} in the scene", index, count)
+            }
+            GameError::InvalidScene { level, problems } => {
+                write!(f, "invalid scene")?;
+                if let Some(level) = level {
+                    write!(f, " {:?}", level)?;
+                }
+                for problem in problems {
+                    write!(f, "\n  {}", problem)?;
+                }
+                Ok(())
+            }
+            GameError::NoSuchLevel { name, known } => {
+                write!(f, "there is no level {:?}, known levels are {}", name, known.join(", "))
+            }
+            GameError::Network { message } => write!(f, "network: {}", message),
+            GameError::Serialize { context, message } => write!(f, "{}: could not serialize: {}", context, message),
+        }
+    }
+}
+
+impl std::error::Error for GameError {}
+
+impl GameError {
+    pub fn config(context: &str, message: impl ToString) -> Self {
+        GameError::Config(ConfigError {
+            context: context.into(),
+            level: None,
+            field: None,
+            expected: None,
+            message: message.to_string(),
+        })
+    }
+
+    pub fn serialize(context: &str, message: impl ToString) -> Self {
+        GameError::Serialize { context: context.into(), message: message.to_string() }
+    }
+}
+
+/// Deserializes `value`, on failure the error tells which field was wrong.
+pub fn parse_config<T: DeserializeOwned>(context: &str, value: Value) -> Result<T, GameError> {
+    let level = value.get("name").and_then(Value::as_str).map(String::from);
+
+    serde_path_to_error::deserialize(value).map_err(|err| {
+        let path = err.path().clone();
+        field_error(context, level, &path, err.into_inner().to_string())
+    })
+}
+
+/// The error for a config that failed to deserialize at `path`, whatever format it came in.
+pub(crate) fn field_error(context: &str, level: Option<String>, path: &Path, message: String) -> GameError {
+    // the root displays as `.`
+    let mut field = match path.iter().next() {
+        Some(_) => path.to_string(),
+        None => String::new(),
+    };
+    // a missing field is reported at the struct that lacks it
+    if let Some(missing) = between(&message, "missing field `", "`") {
+        field = if field.is_empty() { missing.to_string() } else { format!("{}.{}", field, missing) };
+    }
+    let expected = message.rfind("expected ").map(|pos| message[pos + "expected ".len()..].to_string());
+
+    GameError::Config(ConfigError {
+        context: context.into(),
+        level,
+        field: if field.is_empty() { None } else { Some(field) },
+        expected,
+        message,
+    })
+}
+
+fn between<'a>(text: &'a str, start: &str, end: &str) -> Option<&'a str> {
+    let from = text.find(start)? + start.len();
+    let len = text[from..].find(end)?;
+    Some(&text[from..from + len])
+}
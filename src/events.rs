@@ -0,0 +1,30 @@
+use serde_derive::Serialize;
+
+use crate::entities::EntityId;
+
+/// Something that happened during a step which the frontend may want to show, play or count.
+///
+/// Serializes to `{ event: "BananaHitGorilla", banana, gorilla, x, y, damage, health, by }` etc.
+/// Gorillas are referred to by player index, bananas and bricks by their `EntityId`.
+/// `by` is always the gorilla who threw the banana responsible.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event")]
+pub enum GameEvent {
+    /// a banana left the hand of `gorilla` at `x`, `y`
+    ShotFired { gorilla: usize, banana: EntityId, x: f64, y: f64, rot: f64, power: f64 },
+    /// `gorilla` has to wait `remaining` more seconds before throwing again
+    ShotRejectedCooldown { gorilla: usize, remaining: f64 },
+    /// the round starts in `remaining` seconds, nobody throws before
+    ShotRejectedNotStarted { gorilla: usize, remaining: f64 },
+    /// the match doesn't let `gorilla` throw right now, it is not their turn or the round is over or paused
+    ShotRejectedOutOfTurn { gorilla: usize },
+    /// `impulse` in N·s, whether or not it was enough to damage the brick
+    BananaHitBrick { banana: EntityId, brick: EntityId, x: f64, y: f64, impulse: f64, by: Option<usize> },
+    BananaHitGorilla { banana: EntityId, gorilla: usize, x: f64, y: f64, damage: f64, health: f64, by: Option<usize> },
+    BananaHitGround { banana: EntityId, x: f64, y: f64 },
+    /// `by` is `None` if it fell apart without anyone's help, e.g. hit by another brick
+    BrickDestroyed { brick: EntityId, x: f64, y: f64, by: Option<usize> },
+    GorillaDied { gorilla: usize, by: Option<usize> },
+    /// an explosive banana went off at `x`, `y`
+    Explosion { x: f64, y: f64, radius: f64, strength: f64, by: Option<usize> },
+}
@@ -0,0 +1,194 @@
+//! Level files and the registry the frontend picks levels from.
+//!
+//! A level file is a versioned wrapper around a `SceneConfig`, written in JSON or RON:
+//!
+//! ```json
+//! { "version": 1, "name": "mirror", "mirror": true, "scene": { "buildings": [..], .. } }
+//! ```
+
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use std::path::Path;
+
+use crate::error::{field_error, parse_config, ConfigError};
+use crate::{GameError, SceneConfig};
+
+/// The only version of the level format so far.
+pub const LEVEL_FORMAT_VERSION: u64 = 1;
+
+const BUILTIN: &[(&str, &str)] = &[
+    ("stairs.json", include_str!("../levels/stairs.json")),
+    ("valley.json", include_str!("../levels/valley.json")),
+    ("pyramid.json", include_str!("../levels/pyramid.json")),
+    ("mirror.ron", include_str!("../levels/mirror.ron")),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LevelFormat {
+    Json,
+    Ron,
+}
+
+impl LevelFormat {
+    /// Guesses the format from a file name, `None` for anything but `.json` and `.ron`.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "json" => Some(LevelFormat::Json),
+            "ron" => Some(LevelFormat::Ron),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct LevelFile {
+    #[allow(dead_code)]
+    version: u64,
+    name: String,
+    /// append a copy of every building mirrored around x = 0,
+    /// so symmetric levels only list one half
+    #[serde(default)]
+    mirror: bool,
+    scene: SceneConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct Level {
+    pub name: String,
+    pub scene: SceneConfig,
+}
+
+impl Level {
+    pub fn parse(text: &str, format: LevelFormat) -> Result<Level, GameError> {
+        match format {
+            LevelFormat::Json => {
+                let value = serde_json::from_str(text).map_err(|err| GameError::config("load_level", err))?;
+                Level::from_value(value)
+            }
+            LevelFormat::Ron => Level::from_ron(text),
+        }
+    }
+
+    pub fn from_value(value: Value) -> Result<Level, GameError> {
+        let context = "load_level";
+        check_version(context, value.get("version"), value.get("name").and_then(Value::as_str))?;
+        Ok(Level::from_file_contents(parse_config(context, value)?))
+    }
+
+    /// RON goes straight into `LevelFile`, a `Value` in between would lose which enum variant was meant.
+    fn from_ron(text: &str) -> Result<Level, GameError> {
+        let context = "load_level";
+        let syntax = |err: ron::error::SpannedError| {
+            GameError::config(context, format!("{} at line {} column {}", err.code, err.span.start.line, err.span.start.col))
+        };
+
+        // version and name first, a newer format may not fit `LevelFile`
+        let header: Value = ron::from_str(text).map_err(syntax)?;
+        let level = header.get("name").and_then(Value::as_str);
+        check_version(context, header.get("version"), level)?;
+
+        let mut deserializer = ron::Deserializer::from_str(text).map_err(syntax)?;
+        let file = serde_path_to_error::deserialize(&mut deserializer).map_err(|err| {
+            let path = err.path().clone();
+            let err = deserializer.span_error(err.into_inner());
+            let message = format!("{} at line {} column {}", err.code, err.span.start.line, err.span.start.col);
+            field_error(context, level.map(String::from), &path, message)
+        })?;
+        deserializer.end().map_err(|err| syntax(deserializer.span_error(err)))?;
+        Ok(Level::from_file_contents(file))
+    }
+
+    fn from_file_contents(file: LevelFile) -> Level {
+        let mut scene = file.scene;
+        scene.name = Some(file.name.clone());
+        if file.mirror {
+            let mirrored: Vec<_> = scene.buildings.iter()
+                // a building on the axis is its own mirror image
+                .filter(|building| building.x != 0.0)
+                .map(|building| {
+                    let mut building = building.clone();
+                    building.x = -building.x;
+                    building
+                })
+                .collect();
+            scene.buildings.extend(mirrored);
+        }
+
+        Level { name: file.name, scene }
+    }
+
+    /// Reads a level from disk, the format is picked by the file extension.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Level, GameError> {
+        let path = path.as_ref();
+        let context = "load_level";
+        let format = LevelFormat::from_path(path)
+            .ok_or_else(|| GameError::config(context, format!("{} is neither .json nor .ron", path.display())))?;
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| GameError::config(context, format!("{}: {}", path.display(), err)))?;
+        Level::parse(&text, format)
+    }
+}
+
+/// Rejects files of a newer format before serde gets confused by them.
+fn check_version(context: &str, version: Option<&Value>, level: Option<&str>) -> Result<(), GameError> {
+    match version.map(Value::as_u64) {
+        // a missing version is reported by parse_config like any other missing field
+        None | Some(Some(LEVEL_FORMAT_VERSION)) => Ok(()),
+        Some(found) => Err(GameError::Config(ConfigError {
+            context: context.into(),
+            level: level.map(String::from),
+            field: Some("version".into()),
+            expected: Some(LEVEL_FORMAT_VERSION.to_string()),
+            message: format!("unsupported level format version {}", found.map_or("?".into(), |v| v.to_string())),
+        })),
+    }
+}
+
+/// Levels by name, in the order they were added.
+#[derive(Debug, Clone, Default)]
+pub struct LevelRegistry {
+    levels: Vec<Level>,
+}
+
+impl LevelRegistry {
+    pub fn new() -> Self {
+        LevelRegistry::default()
+    }
+
+    /// The levels shipped with the game, from `levels/`.
+    pub fn builtin() -> Result<Self, GameError> {
+        let mut registry = LevelRegistry::new();
+        for (file, text) in BUILTIN {
+            let format = LevelFormat::from_path(file).expect("built-in levels are .json or .ron");
+            registry.add(Level::parse(text, format)?);
+        }
+        Ok(registry)
+    }
+
+    /// Adds `level`, replacing a level of the same name.
+    pub fn add(&mut self, level: Level) {
+        match self.levels.iter_mut().find(|known| known.name == level.name) {
+            Some(known) => *known = level,
+            None => self.levels.push(level),
+        }
+    }
+
+    pub fn levels(&self) -> &[Level] {
+        &self.levels
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.levels.iter().map(|level| level.name.as_str()).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Result<&SceneConfig, GameError> {
+        self.levels.iter()
+            .find(|level| level.name == name)
+            .map(|level| &level.scene)
+            .ok_or_else(|| GameError::NoSuchLevel {
+                name: name.into(),
+                known: self.names().into_iter().map(String::from).collect(),
+            })
+    }
+}
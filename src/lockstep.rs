@@ -0,0 +1,165 @@
+//! Lockstep: no peer runs a frame before the inputs of both players for it have arrived.
+//!
+//! Inputs take effect `input_delay` frames after they were made, which hides latency up to that long.
+//! Beyond that the match stalls until the inputs are in. Every frame is one physics tick.
+
+use serde_derive::{Serialize, Deserialize};
+
+use std::collections::BTreeMap;
+
+use crate::net::{play_frame, Input, Message, Peer, Received, Transport, INPUT_HISTORY};
+use crate::simulation::Simulation;
+use crate::{warn, GameError};
+
+/// How a `LockstepSession` plays, both peers need the same.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LockstepConfig {
+    /// frames between making an input and it taking effect (default: `3`)
+    pub input_delay: Option<u64>,
+    /// frames between two checksum comparisons, `0` never compares (default: `30`)
+    pub checksum_interval: Option<u64>,
+}
+
+/// What the UI may want to show about the connection.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LockstepStatus {
+    /// the next frame to run, `None` until both peers are in
+    pub frame: Option<u64>,
+    /// stalled for inputs of the other player
+    pub waiting: bool,
+    /// the last frame on which the peers disagreed
+    pub desync: Option<u64>,
+}
+
+/// One peer of a two player lockstep match. Player 0 hosts: its state is the one both start from.
+pub struct LockstepSession<T: Transport> {
+    peer: Peer<T>,
+    /// what the other side last heard from us about `next_remote`
+    acked: u64,
+    /// inputs of both players by frame
+    inputs: BTreeMap<u64, [Option<Vec<Input>>; 2]>,
+}
+
+impl<T: Transport> LockstepSession<T> {
+    /// `local` is the gorilla this peer plays, `0` or `1`.
+    pub fn new(transport: T, local: usize, config: &LockstepConfig) -> Result<Self, GameError> {
+        let peer = Peer::new(transport, local, config.input_delay.unwrap_or(3), config.checksum_interval.unwrap_or(30))?;
+        Ok(LockstepSession { peer, acked: 0, inputs: BTreeMap::new() })
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.peer.transport
+    }
+
+    /// The player of this peer.
+    pub fn local(&self) -> usize {
+        self.peer.local
+    }
+
+    pub fn status(&self) -> LockstepStatus {
+        LockstepStatus { frame: self.peer.frame, waiting: self.peer.waiting, desync: self.peer.desync }
+    }
+
+    /// Something the local player did, it takes effect `input_delay` frames from now on both peers.
+    pub fn input(&mut self, input: Input) {
+        self.peer.input(input);
+    }
+
+    /// Handles whatever arrived and runs the frames `dt` seconds of real time are worth, as far as the inputs allow.
+    pub fn step(&mut self, sim: &mut Simulation, dt: f64) -> Result<(), GameError> {
+        for message in self.peer.transport.receive() {
+            self.handle(sim, message)?;
+        }
+        let ts = sim.world().timestep();
+        if self.peer.frame.is_none() {
+            return Ok(());
+        }
+
+        self.peer.elapse(dt, ts);
+        while self.peer.accumulator >= ts {
+            let frame = self.peer.frame.unwrap_or(0);
+            self.schedule(frame);
+            if !self.ready(frame) {
+                self.peer.waiting = true;
+                // ours may have been lost on the way, theirs may be waiting for them
+                self.peer.send_unacked();
+                break;
+            }
+            self.run(sim, frame);
+            self.peer.accumulator -= ts;
+        }
+        if self.peer.next_remote > self.acked {
+            self.acked = self.peer.next_remote;
+            self.peer.transport.send(&Message::Ack { frame: self.peer.next_remote - 1 });
+        }
+        Ok(())
+    }
+
+    /// Sends the local inputs as soon as they have a frame.
+    fn schedule(&mut self, frame: u64) {
+        let local = self.peer.local;
+        for (frame, inputs) in self.peer.schedule(frame) {
+            self.peer.transport.send(&Message::Input { player: local, frame, inputs: inputs.clone() });
+            self.inputs.entry(frame).or_default()[local] = Some(inputs);
+        }
+    }
+
+    fn ready(&self, frame: u64) -> bool {
+        self.inputs.get(&frame).is_some_and(|inputs| inputs.iter().all(Option::is_some))
+    }
+
+    fn run(&mut self, sim: &mut Simulation, frame: u64) {
+        let inputs = self.inputs.get(&frame).map_or([None, None], |inputs| [inputs[0].as_ref(), inputs[1].as_ref()]);
+        play_frame(sim, inputs);
+        self.peer.frame = Some(frame + 1);
+
+        let forgotten = frame.saturating_sub(INPUT_HISTORY);
+        self.inputs = self.inputs.split_off(&forgotten);
+
+        if self.peer.checksum_interval > 0 && frame.is_multiple_of(self.peer.checksum_interval) && self.peer.checksum(frame, sim.checksum()) {
+            self.resync_or_warn(sim);
+        }
+    }
+
+    fn handle(&mut self, sim: &mut Simulation, message: Message) -> Result<(), GameError> {
+        match self.peer.receive(message)? {
+            Received::Nothing => {}
+            Received::Resync => {
+                if self.peer.frame.is_none() {
+                    self.resync(sim)?;
+                } else {
+                    self.resync_or_warn(sim);
+                }
+            }
+            Received::Inputs { frame, inputs } => {
+                let remote = self.peer.remote();
+                self.inputs.entry(frame).or_default()[remote] = Some(inputs);
+                while self.inputs.get(&self.peer.next_remote).is_some_and(|inputs| inputs[remote].is_some()) {
+                    self.peer.next_remote += 1;
+                }
+            }
+            Received::Resynced { frame, epoch, snapshot } => {
+                sim.restore(&snapshot)?;
+                self.peer.go_on_from(frame, epoch);
+            }
+        }
+        Ok(())
+    }
+
+    /// Player 0 only: makes its own state the one both go on from at the next frame. It restores that state as well,
+    /// a restored world has to run against a restored world.
+    fn resync(&mut self, sim: &mut Simulation) -> Result<(), GameError> {
+        let frame = self.peer.frame.unwrap_or(0);
+        let snapshot = sim.snapshot();
+        sim.restore(&snapshot)?;
+        let epoch = self.peer.resync(frame, snapshot);
+        self.peer.go_on_from(frame, epoch);
+        Ok(())
+    }
+
+    fn resync_or_warn(&mut self, sim: &mut Simulation) {
+        if let Err(err) = self.resync(sim) {
+            warn!("cannot resync: {}", err);
+        }
+    }
+}
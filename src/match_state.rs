@@ -0,0 +1,324 @@
+use serde_derive::{Serialize, Deserialize};
+
+use crate::checksum::Fnv;
+
+/// How a match is played, part of the `GameConfig`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Rules {
+    /// rounds a player has to win to win the match (default: `3`)
+    pub rounds_to_win: Option<usize>,
+    /// seconds before each round starts (default: `3`)
+    pub countdown: Option<f64>,
+    /// seconds a finished round stays on screen before the next one (default: `3`)
+    pub round_over_delay: Option<f64>,
+    /// (default: `RealTime`)
+    pub mode: Option<Mode>,
+    /// seconds the active player has to throw in `TurnBased` mode before the turn passes (default: no limit)
+    pub turn_time: Option<f64>,
+    /// seconds a throw in `TurnBased` mode waits at most for the world to settle before the turn passes anyway (default: `10`)
+    pub resolve_time: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Mode {
+    /// everyone throws whenever their cooldown allows
+    RealTime,
+    /// like the original: one throw per turn, the turn passes once the dust has settled
+    TurnBased,
+}
+
+/// Serializes to `{ player, phase: "Aiming", remaining }`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Turn {
+    pub player: usize,
+    #[serde(flatten)]
+    pub phase: TurnPhase,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "phase")]
+pub enum TurnPhase {
+    /// `remaining` is `None` without a turn timer
+    Aiming { remaining: Option<f64> },
+    /// the banana is out, waiting for it to expire and the world to settle, for `remaining` seconds at most
+    Resolving { remaining: f64 },
+}
+
+/// Serializes to `{ state: "Countdown", remaining }` etc.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state")]
+pub enum MatchState {
+    /// free play, nothing counts until `start_match`
+    Lobby,
+    Countdown { remaining: f64 },
+    Playing,
+    /// `winner` is `None` if both went down in the same tick
+    RoundOver { winner: Option<usize>, remaining: f64 },
+    MatchOver { winner: usize },
+    Paused,
+}
+
+/// What the UI needs to know about the match.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MatchStatus {
+    pub state: MatchState,
+    pub scores: Vec<usize>,
+    pub round: usize,
+    pub rounds_to_win: usize,
+    /// only in `TurnBased` mode while playing
+    pub turn: Option<Turn>,
+}
+
+/// Lobby → Countdown → Playing → RoundOver → (Countdown | MatchOver), any of them can be paused.
+///
+/// Only keeps score and time, the `Simulation` rebuilds the scene when told to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Match {
+    rules: Rules,
+    state: MatchState,
+    /// what to go back to when unpausing
+    paused_in: Option<MatchState>,
+    scores: Vec<usize>,
+    round: usize,
+    turn: Option<Turn>,
+}
+
+impl Match {
+    pub fn new(rules: Rules) -> Self {
+        Match { rules, state: MatchState::Lobby, paused_in: None, scores: vec![0, 0], round: 0, turn: None }
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn state(&self) -> &MatchState {
+        &self.state
+    }
+
+    pub fn scores(&self) -> &[usize] {
+        &self.scores
+    }
+
+    pub fn round(&self) -> usize {
+        self.round
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.rules.mode.unwrap_or(Mode::RealTime)
+    }
+
+    pub fn turn(&self) -> Option<&Turn> {
+        self.turn.as_ref()
+    }
+
+    pub fn rounds_to_win(&self) -> usize {
+        self.rules.rounds_to_win.unwrap_or(3)
+    }
+
+    pub fn status(&self) -> MatchStatus {
+        MatchStatus {
+            state: self.state.clone(),
+            scores: self.scores.clone(),
+            round: self.round,
+            rounds_to_win: self.rounds_to_win(),
+            turn: self.turn.clone(),
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state == MatchState::Paused
+    }
+
+    /// Everything that decides how the match goes on, rules included, they come along with a snapshot.
+    pub fn checksum(&self, hash: &mut Fnv) {
+        let rules = &self.rules;
+        hash.write_opt_usize(rules.rounds_to_win);
+        hash.write_opt_f64(rules.countdown);
+        hash.write_opt_f64(rules.round_over_delay);
+        hash.write_opt_usize(rules.mode.map(|mode| mode as usize));
+        hash.write_opt_f64(rules.turn_time);
+        hash.write_opt_f64(rules.resolve_time);
+
+        state_checksum(&self.state, hash);
+        match &self.paused_in {
+            Some(state) => {
+                hash.write(&[1]);
+                state_checksum(state, hash);
+            }
+            None => hash.write(&[0]),
+        }
+        hash.write_u64(self.scores.len() as u64);
+        for &score in &self.scores {
+            hash.write_u64(score as u64);
+        }
+        hash.write_u64(self.round as u64);
+        match &self.turn {
+            Some(Turn { player, phase: TurnPhase::Aiming { remaining } }) => {
+                hash.write(&[1]);
+                hash.write_u64(*player as u64);
+                hash.write_opt_f64(*remaining);
+            }
+            Some(Turn { player, phase: TurnPhase::Resolving { remaining } }) => {
+                hash.write(&[2]);
+                hash.write_u64(*player as u64);
+                hash.write_f64(*remaining);
+            }
+            None => hash.write(&[0]),
+        }
+    }
+
+    /// Throwing is allowed while playing and for practice in the lobby,
+    /// in `TurnBased` mode only for the active player.
+    pub fn can_shoot(&self, player: usize) -> bool {
+        match self.state {
+            MatchState::Lobby => true,
+            MatchState::Playing => match self.turn {
+                Some(Turn { player: active, phase: TurnPhase::Aiming { .. } }) => active == player,
+                Some(Turn { phase: TurnPhase::Resolving { .. }, .. }) => false,
+                None => true,
+            },
+            _ => false,
+        }
+    }
+
+    /// Ends the aiming phase of the active player.
+    pub fn shot_fired(&mut self) {
+        if self.state == MatchState::Playing {
+            let remaining = self.rules.resolve_time.unwrap_or(10.0);
+            if let Some(turn) = self.turn.as_mut() {
+                turn.phase = TurnPhase::Resolving { remaining };
+            }
+        }
+    }
+
+    /// Starts a new match from the lobby or after the last one ended,
+    /// returns `false` if a match is already running.
+    pub fn start(&mut self) -> bool {
+        match self.state {
+            MatchState::Lobby | MatchState::MatchOver { .. } => {
+                self.scores = vec![0; self.scores.len()];
+                self.round = 1;
+                self.state = self.countdown();
+                self.turn = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if !self.is_paused() {
+            self.paused_in = Some(std::mem::replace(&mut self.state, MatchState::Paused));
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if let Some(state) = self.paused_in.take() {
+            self.state = state;
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.is_paused() { self.resume() } else { self.pause() }
+    }
+
+    /// Advances the timers by one tick of `dt` and ends the round once a gorilla is down.
+    /// `settled` tells whether all bananas are gone and nothing moves anymore.
+    ///
+    /// Returns `true` when a new round begins and the scene has to be rebuilt.
+    pub fn update(&mut self, dt: f64, alive: &[bool], settled: bool) -> bool {
+        match self.state {
+            MatchState::Countdown { remaining } => {
+                if remaining - dt <= 0.0 {
+                    self.state = MatchState::Playing;
+                    if self.mode() == Mode::TurnBased {
+                        // players take turns opening the rounds
+                        self.turn = Some(self.aiming((self.round - 1) % self.scores.len()));
+                    }
+                } else {
+                    self.state = MatchState::Countdown { remaining: remaining - dt };
+                }
+            }
+            MatchState::Playing => {
+                let standing: Vec<usize> = (0..alive.len()).filter(|&i| alive[i]).collect();
+                if standing.len() < alive.len() {
+                    let winner = if standing.len() == 1 { Some(standing[0]) } else { None };
+                    if let Some(winner) = winner {
+                        self.scores[winner] += 1;
+                    }
+                    self.state = MatchState::RoundOver { winner, remaining: self.rules.round_over_delay.unwrap_or(3.0) };
+                    self.turn = None;
+                } else {
+                    self.update_turn(dt, settled);
+                }
+            }
+            MatchState::RoundOver { winner, remaining } => {
+                if remaining - dt > 0.0 {
+                    self.state = MatchState::RoundOver { winner, remaining: remaining - dt };
+                } else if let Some(winner) = winner.filter(|&w| self.scores[w] >= self.rounds_to_win()) {
+                    self.state = MatchState::MatchOver { winner };
+                } else {
+                    self.round += 1;
+                    self.state = self.countdown();
+                    return true;
+                }
+            }
+            MatchState::Lobby | MatchState::MatchOver { .. } | MatchState::Paused => {}
+        }
+        false
+    }
+
+    fn update_turn(&mut self, dt: f64, settled: bool) {
+        let players = self.scores.len();
+        let next = match self.turn {
+            Some(Turn { player, phase: TurnPhase::Aiming { remaining: Some(remaining) } }) => {
+                if remaining - dt <= 0.0 {
+                    // too slow, the turn is forfeit
+                    Some(self.aiming((player + 1) % players))
+                } else {
+                    Some(Turn { player, phase: TurnPhase::Aiming { remaining: Some(remaining - dt) } })
+                }
+            }
+            Some(Turn { player, phase: TurnPhase::Resolving { remaining } }) => {
+                if settled || remaining - dt <= 0.0 {
+                    // something that never comes to rest must not hold up the match
+                    Some(self.aiming((player + 1) % players))
+                } else {
+                    Some(Turn { player, phase: TurnPhase::Resolving { remaining: remaining - dt } })
+                }
+            }
+            ref turn => turn.clone(),
+        };
+        self.turn = next;
+    }
+
+    fn aiming(&self, player: usize) -> Turn {
+        Turn { player, phase: TurnPhase::Aiming { remaining: self.rules.turn_time } }
+    }
+
+    fn countdown(&self) -> MatchState {
+        MatchState::Countdown { remaining: self.rules.countdown.unwrap_or(3.0) }
+    }
+}
+
+fn state_checksum(state: &MatchState, hash: &mut Fnv) {
+    match *state {
+        MatchState::Lobby => hash.write(&[0]),
+        MatchState::Countdown { remaining } => {
+            hash.write(&[1]);
+            hash.write_f64(remaining);
+        }
+        MatchState::Playing => hash.write(&[2]),
+        MatchState::RoundOver { winner, remaining } => {
+            hash.write(&[3]);
+            hash.write_opt_usize(winner);
+            hash.write_f64(remaining);
+        }
+        MatchState::MatchOver { winner } => {
+            hash.write(&[4]);
+            hash.write_u64(winner as u64);
+        }
+        MatchState::Paused => hash.write(&[5]),
+    }
+}
@@ -0,0 +1,312 @@
+//! What peers of a networked match tell each other, and the transports that carry it.
+//!
+//! Peers only exchange inputs, every peer runs the whole simulation. That only works because
+//! the simulation is deterministic: equal starting states and equal inputs on equal frames give equal matches.
+
+use serde_derive::{Serialize, Deserialize};
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::rc::Rc;
+
+use crate::simulation::Simulation;
+use crate::{warn, GameError, Point, Shot, Snapshot};
+
+/// Bumped whenever `Message` or the simulation changes in a way old peers would not agree with.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// frames a peer may run in one `step` to catch up after a stall
+const MAX_CATCH_UP: u64 = 5;
+/// inputs this many frames old are still kept, to run them again after a resync
+pub(crate) const INPUT_HISTORY: u64 = 120;
+
+/// Serializes to `{ msg: "Input", player, frame, inputs }` etc.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "msg")]
+pub enum Message {
+    /// first thing a peer says, `player` is the gorilla it plays
+    Join { player: usize, protocol: u32 },
+    /// everything `player` did on `frame`, sent for every frame even if it is nothing
+    Input { player: usize, frame: u64, inputs: Vec<Input> },
+    /// `Simulation::checksum` after `frame`, `epoch` counts the resyncs before it
+    Checksum { frame: u64, checksum: u32, epoch: u32 },
+    /// inputs up to and including `frame` arrived, no need to send them again
+    Ack { frame: u64 },
+    /// the state to go on from at `frame`, sent by player 0 to start and after a desync
+    Resync { frame: u64, epoch: u32, snapshot: Box<Snapshot> },
+}
+
+/// Something a player does that changes the match.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "input")]
+pub enum Input {
+    /// shots for another gorilla than the player's are ignored
+    Shoot { shot: Shot },
+    /// moves the player's own gorilla
+    MoveGorilla { point: Point },
+    StartMatch,
+    TogglePause,
+}
+
+impl Input {
+    /// Applies what `player` did, inputs arrive from the network so they are checked, not trusted.
+    pub fn apply(&self, sim: &mut Simulation, player: usize) {
+        match self {
+            Input::Shoot { shot } if shot.gorilla_id == player => sim.shoot(shot),
+            Input::Shoot { shot } => {
+                warn!("player {} tried to throw for gorilla {}", player, shot.gorilla_id);
+            }
+            Input::MoveGorilla { point } => {
+                if let Err(err) = sim.move_gorilla(player, *point) {
+                    warn!("cannot move gorilla of player {}: {}", player, err);
+                }
+            }
+            Input::StartMatch => {
+                if let Err(err) = sim.start_match() {
+                    warn!("cannot start the match: {}", err);
+                }
+            }
+            Input::TogglePause => sim.toggle_pause(),
+        }
+    }
+}
+
+/// Applies the inputs both players made for a frame and runs it.
+pub(crate) fn play_frame(sim: &mut Simulation, inputs: [Option<&Vec<Input>>; 2]) {
+    for (player, inputs) in inputs.iter().enumerate() {
+        for input in inputs.iter().flat_map(|inputs| inputs.iter()) {
+            input.apply(sim, player);
+        }
+    }
+    // frames go on while paused, someone has to be able to unpause
+    if !sim.game_match().is_paused() {
+        sim.run_ticks(1);
+    }
+}
+
+/// What a session still has to do about a message once `Peer::receive` has seen it.
+pub(crate) enum Received {
+    Nothing,
+    /// player 0 only: the other side joined or disagrees, send it the state both go on from
+    Resync,
+    /// the other player's inputs for `frame`
+    Inputs { frame: u64, inputs: Vec<Input> },
+    /// player 1 only: the host says to go on from `snapshot` at `frame`
+    Resynced { frame: u64, epoch: u32, snapshot: Box<Snapshot> },
+}
+
+/// The part of a session that doesn't depend on how it deals with late inputs: the handshake,
+/// local inputs on their way to the other side and the checksums both peers compare.
+pub(crate) struct Peer<T: Transport> {
+    pub transport: T,
+    pub local: usize,
+    pub input_delay: u64,
+    pub checksum_interval: u64,
+    /// the next frame to run, `None` until the match is set up
+    pub frame: Option<u64>,
+    /// the next frame the local player's inputs go to
+    pub next_local: u64,
+    /// the first frame the other player's inputs are missing for
+    pub next_remote: u64,
+    /// local inputs not yet scheduled for a frame
+    pending: Vec<Input>,
+    /// local inputs the other side hasn't confirmed yet
+    pub unacked: BTreeMap<u64, Vec<Input>>,
+    local_checksums: BTreeMap<u64, u32>,
+    remote_checksums: BTreeMap<u64, u32>,
+    /// resyncs so far, checksums from before the last one are stale
+    pub epoch: u32,
+    pub desync: Option<u64>,
+    pub waiting: bool,
+    pub accumulator: f64,
+}
+
+impl<T: Transport> Peer<T> {
+    /// `local` is the gorilla this peer plays, `0` or `1`.
+    pub fn new(mut transport: T, local: usize, input_delay: u64, checksum_interval: u64) -> Result<Self, GameError> {
+        if local > 1 {
+            return Err(GameError::Network { message: format!("there are only players 0 and 1, not {}", local) });
+        }
+        transport.send(&Message::Join { player: local, protocol: PROTOCOL_VERSION });
+        Ok(Peer {
+            transport,
+            local,
+            input_delay,
+            checksum_interval,
+            frame: None,
+            next_local: 0,
+            next_remote: 0,
+            pending: Vec::new(),
+            unacked: BTreeMap::new(),
+            local_checksums: BTreeMap::new(),
+            remote_checksums: BTreeMap::new(),
+            epoch: 0,
+            desync: None,
+            waiting: false,
+            accumulator: 0.0,
+        })
+    }
+
+    pub fn remote(&self) -> usize {
+        1 - self.local
+    }
+
+    pub fn input(&mut self, input: Input) {
+        self.pending.push(input);
+    }
+
+    /// Adds `dt` seconds of real time, at most `MAX_CATCH_UP` frames worth.
+    pub fn elapse(&mut self, dt: f64, timestep: f64) {
+        self.accumulator = f64::min(self.accumulator + dt, timestep * MAX_CATCH_UP as f64);
+        self.waiting = false;
+    }
+
+    /// Hands the pending local inputs to the first frame that has none yet, and empty ones to the rest up to `input_delay` ahead.
+    /// Returns the frames that got their inputs just now.
+    pub fn schedule(&mut self, frame: u64) -> Vec<(u64, Vec<Input>)> {
+        let mut scheduled = Vec::new();
+        while self.next_local <= frame + self.input_delay {
+            let inputs = std::mem::take(&mut self.pending);
+            self.unacked.insert(self.next_local, inputs.clone());
+            scheduled.push((self.next_local, inputs));
+            self.next_local += 1;
+        }
+        scheduled
+    }
+
+    /// Sends the local inputs again that may have been lost on the way.
+    pub fn send_unacked(&mut self) {
+        for (&frame, inputs) in &self.unacked {
+            self.transport.send(&Message::Input { player: self.local, frame, inputs: inputs.clone() });
+        }
+    }
+
+    /// Tells the other side our checksum after `frame`, `true` if this peer has to resync.
+    pub fn checksum(&mut self, frame: u64, checksum: u32) -> bool {
+        self.local_checksums.insert(frame, checksum);
+        self.transport.send(&Message::Checksum { frame, checksum, epoch: self.epoch });
+        self.compare(frame)
+    }
+
+    /// Deals with the handshake, checksums and acks, and hands the rest to the session.
+    pub fn receive(&mut self, message: Message) -> Result<Received, GameError> {
+        match message {
+            Message::Join { player, protocol } => {
+                if protocol != PROTOCOL_VERSION {
+                    return Err(GameError::Network { message: format!("the other side speaks protocol {}, we speak {}", protocol, PROTOCOL_VERSION) });
+                }
+                if player != self.remote() {
+                    return Err(GameError::Network { message: format!("both sides want to play gorilla {}", player) });
+                }
+                if self.local == 0 && self.frame.is_none() {
+                    return Ok(Received::Resync);
+                }
+            }
+            Message::Input { player, frame, inputs } => {
+                if player != self.remote() {
+                    warn!("ignoring inputs of player {} from the other side", player);
+                    return Ok(Received::Nothing);
+                }
+                return Ok(Received::Inputs { frame, inputs });
+            }
+            Message::Checksum { frame, checksum, epoch } => {
+                if epoch == self.epoch {
+                    self.remote_checksums.insert(frame, checksum);
+                    if self.compare(frame) {
+                        return Ok(Received::Resync);
+                    }
+                }
+            }
+            Message::Ack { frame } => {
+                self.unacked = self.unacked.split_off(&(frame + 1));
+            }
+            Message::Resync { frame, epoch, snapshot } => {
+                if self.local == 0 {
+                    warn!("ignoring a resync, the host decides what the state is");
+                    return Ok(Received::Nothing);
+                }
+                return Ok(Received::Resynced { frame, epoch, snapshot });
+            }
+        }
+        Ok(Received::Nothing)
+    }
+
+    /// Player 0 only: sends the state to go on from at `frame`, returns the epoch that starts with it.
+    pub fn resync(&mut self, frame: u64, snapshot: Snapshot) -> u32 {
+        let epoch = self.epoch + 1;
+        self.transport.send(&Message::Resync { frame, epoch, snapshot: Box::new(snapshot) });
+        epoch
+    }
+
+    pub fn go_on_from(&mut self, frame: u64, epoch: u32) {
+        self.frame = Some(frame);
+        self.epoch = epoch;
+        self.next_local = u64::max(self.next_local, frame);
+        self.next_remote = u64::max(self.next_remote, frame);
+        self.local_checksums.clear();
+        self.remote_checksums.clear();
+        self.accumulator = 0.0;
+    }
+
+    /// `true` if the checksums of `frame` differ and this peer is the one to resync.
+    fn compare(&mut self, frame: u64) -> bool {
+        let (local, remote) = match (self.local_checksums.get(&frame), self.remote_checksums.get(&frame)) {
+            (Some(&local), Some(&remote)) => (local, remote),
+            _ => return false,
+        };
+        self.local_checksums = self.local_checksums.split_off(&(frame + 1));
+        self.remote_checksums = self.remote_checksums.split_off(&(frame + 1));
+        if local == remote {
+            return false;
+        }
+        warn!("desync on frame {}: {:08x} here, {:08x} there", frame, local, remote);
+        self.desync = Some(frame);
+        self.local == 0
+    }
+}
+
+/// Carries messages to the other peer.
+///
+/// Inputs may arrive out of order or not at all, they are sent again until acknowledged.
+/// `Join` and `Resync` have to make it through, like they do over a WebSocket.
+pub trait Transport {
+    fn send(&mut self, message: &Message);
+    /// Messages that arrived since the last call, oldest first.
+    fn receive(&mut self) -> Vec<Message>;
+}
+
+type Queue = Rc<RefCell<VecDeque<String>>>;
+
+/// Both ends of an in-process connection, for tests and local play.
+///
+/// Messages still go through JSON, so anything that doesn't survive the wire doesn't survive this either.
+pub struct Loopback {
+    outbox: Queue,
+    inbox: Queue,
+}
+
+impl Loopback {
+    pub fn pair() -> (Loopback, Loopback) {
+        let (a, b): (Queue, Queue) = Default::default();
+        (Loopback { outbox: a.clone(), inbox: b.clone() }, Loopback { outbox: b, inbox: a })
+    }
+}
+
+impl Transport for Loopback {
+    fn send(&mut self, message: &Message) {
+        match serde_json::to_string(message) {
+            Ok(text) => self.outbox.borrow_mut().push_back(text),
+            Err(err) => {
+                warn!("cannot send {:?}: {}", message, err);
+            }
+        }
+    }
+
+    fn receive(&mut self) -> Vec<Message> {
+        self.inbox.borrow_mut().drain(..)
+            .filter_map(|text| serde_json::from_str(&text).map_err(|err| {
+                warn!("dropping a message: {}", err);
+            }).ok())
+            .collect()
+    }
+}
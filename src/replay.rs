@@ -0,0 +1,236 @@
+//! Everything done to a `Simulation` from outside, and playing it back.
+//!
+//! The simulation only depends on its config and on these inputs, so a replay played on a fresh
+//! simulation made from the same config ends up bit for bit where the original did.
+
+use serde_derive::{Serialize, Deserialize};
+
+use crate::checksum::{Divergence, EntityChecksums};
+use crate::simulation::Simulation;
+use crate::{GameConfig, GameError, Point, SceneConfig, Shot, Snapshot};
+
+/// Serializes to `{ action: "Shoot", shot, spin }` etc.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action")]
+pub enum Action {
+    SetScene { scene: Box<SceneConfig> },
+    ClearScene,
+    Reset,
+    SetSeed { seed: u64 },
+    /// a seed was drawn from the simulation's generator, e.g. for a random level
+    RollSeed,
+    StartMatch,
+    Pause,
+    Resume,
+    TogglePause,
+    /// `spin` is what the banana got, `None` if the shot was rejected
+    Shoot { shot: Shot, spin: Option<f64> },
+    MoveGorilla { index: usize, point: Point },
+    Explode { x: f64, y: f64, radius: f64, strength: f64, damage: f64, by: Option<usize> },
+    Restore { snapshot: Box<Snapshot> },
+    /// the world was rebuilt from its own snapshot, rollback starts every frame like that
+    Reload,
+    /// physics ticks in a row with nothing else in between
+    Ticks { count: u64 },
+}
+
+/// How far a replay had got, to cut it back to that.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Mark {
+    actions: usize,
+    /// the last action if it was `Ticks`, later ticks get merged into it
+    ticks: Option<u64>,
+    checksums: usize,
+    entity_checksums: usize,
+}
+
+/// What a `Simulation` was made from and everything done to it since, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub config: GameConfig,
+    pub actions: Vec<Action>,
+    /// `Simulation::checksum` after every tick, starting with the first
+    #[serde(default)]
+    pub checksums: Vec<u32>,
+    /// every `entity_checksum_interval` ticks
+    #[serde(default)]
+    pub entity_checksums: Vec<EntityChecksums>,
+}
+
+impl Replay {
+    pub fn new(config: GameConfig) -> Self {
+        Replay { config, actions: Vec::new(), checksums: Vec::new(), entity_checksums: Vec::new() }
+    }
+
+    /// Ticks right after ticks are merged into one action.
+    pub fn push(&mut self, action: Action) {
+        if let (Some(Action::Ticks { count }), Action::Ticks { count: more }) = (self.actions.last_mut(), &action) {
+            *count += more;
+            return;
+        }
+        self.actions.push(action);
+    }
+
+    pub(crate) fn mark(&self) -> Mark {
+        let ticks = match self.actions.last() {
+            Some(Action::Ticks { count }) => Some(*count),
+            _ => None,
+        };
+        Mark { actions: self.actions.len(), ticks, checksums: self.checksums.len(), entity_checksums: self.entity_checksums.len() }
+    }
+
+    /// Forgets everything recorded since `mark`.
+    pub(crate) fn truncate(&mut self, mark: Mark) {
+        self.actions.truncate(mark.actions);
+        if let (Some(Action::Ticks { count }), Some(ticks)) = (self.actions.last_mut(), mark.ticks) {
+            *count = ticks;
+        }
+        self.checksums.truncate(mark.checksums);
+        self.entity_checksums.truncate(mark.entity_checksums);
+    }
+
+    /// Physics ticks in the whole replay.
+    pub fn ticks(&self) -> u64 {
+        self.actions.iter().map(|action| match action {
+            Action::Ticks { count } => *count,
+            _ => 0,
+        }).sum()
+    }
+
+    /// Plays the replay and checks every tick against the recorded checksums,
+    /// `None` if they all match or there are none.
+    pub fn verify(&self) -> Result<Option<Divergence>, GameError> {
+        let mut player = ReplayPlayer::new(self.clone());
+        let mut sim = player.simulation();
+        let mut divergence: Option<Divergence> = None;
+
+        while !player.is_finished() {
+            let before = sim.ticks();
+            player.advance(&mut sim, 1)?;
+            let tick = sim.ticks();
+            if tick == before {
+                continue;
+            }
+            if divergence.is_none() {
+                let expected = match self.checksums.get(tick as usize - 1) {
+                    Some(&expected) => expected,
+                    None => break,
+                };
+                let actual = sim.checksum();
+                if actual != expected {
+                    divergence = Some(Divergence { tick, expected, actual, entity: None });
+                }
+            }
+            // find out who it was at the first per entity checksums since
+            if let Some(divergence) = divergence.as_mut() {
+                if !self.entity_checksums.iter().any(|checksums| checksums.tick >= tick) {
+                    break;
+                }
+                if let Some(expected) = self.entity_checksums.iter().find(|checksums| checksums.tick == tick) {
+                    let actual = sim.entity_checksums();
+                    let differs = |i: usize| expected.entities.get(i).cloned() != actual.get(i).map(|&(_, checksum)| checksum);
+                    let first = (0..usize::max(expected.entities.len(), actual.len())).find(|&i| differs(i));
+                    divergence.entity = first.and_then(|i| actual.get(i)).map(|&(id, _)| id);
+                    break;
+                }
+            }
+        }
+        Ok(divergence)
+    }
+
+    /// Plays the whole replay on a fresh simulation.
+    pub fn play(&self) -> Result<Simulation, GameError> {
+        let mut player = ReplayPlayer::new(self.clone());
+        let mut sim = player.simulation();
+        player.advance(&mut sim, u64::MAX)?;
+        Ok(sim)
+    }
+}
+
+/// Plays a replay a few ticks at a time, to watch it at the speed it was played.
+pub struct ReplayPlayer {
+    replay: Replay,
+    /// the action to play next
+    next: usize,
+    /// ticks of `next` already played, if it is `Ticks`
+    played: u64,
+    /// real time not yet consumed by a whole tick
+    accumulator: f64,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayer { replay, next: 0, played: 0, accumulator: 0.0 }
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// A fresh simulation to play the replay on.
+    pub fn simulation(&self) -> Simulation {
+        Simulation::new(self.replay.config.clone())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.replay.actions.len()
+    }
+
+    /// Plays `dt` seconds of real time, in whole ticks of the simulation's timestep.
+    pub fn step(&mut self, sim: &mut Simulation, dt: f64) -> Result<(), GameError> {
+        let ts = sim.world().timestep();
+        self.accumulator += dt;
+        let ticks = (self.accumulator / ts).floor();
+        self.accumulator -= ticks * ts;
+        self.advance(sim, ticks as u64)
+    }
+
+    /// Plays up to `ticks` physics ticks on `sim`, along with every action recorded before them.
+    /// Whatever was recorded after the last of them waits for the next call.
+    pub fn advance(&mut self, sim: &mut Simulation, mut ticks: u64) -> Result<(), GameError> {
+        while let Some(action) = self.replay.actions.get(self.next) {
+            if ticks == 0 {
+                break;
+            }
+            if let Action::Ticks { count } = *action {
+                let now = u64::min(count - self.played, ticks);
+                sim.run_ticks(now);
+                self.played += now;
+                ticks -= now;
+                if self.played < count {
+                    break;
+                }
+                self.played = 0;
+            } else {
+                play(sim, action)?;
+            }
+            self.next += 1;
+        }
+        Ok(())
+    }
+}
+
+fn play(sim: &mut Simulation, action: &Action) -> Result<(), GameError> {
+    match action {
+        Action::SetScene { scene } => sim.set_scene(scene)?,
+        Action::ClearScene => sim.clear_scene(),
+        Action::Reset => sim.reset()?,
+        Action::SetSeed { seed } => sim.set_seed(*seed),
+        Action::RollSeed => {
+            sim.roll_seed();
+        }
+        Action::StartMatch => sim.start_match()?,
+        Action::Pause => sim.pause(),
+        Action::Resume => sim.resume(),
+        Action::TogglePause => sim.toggle_pause(),
+        Action::Shoot { shot, spin } => sim.shoot_with_spin(shot, *spin),
+        Action::MoveGorilla { index, point } => sim.move_gorilla(*index, *point)?,
+        Action::Explode { x, y, radius, strength, damage, by } => {
+            sim.explode(nalgebra::Vector2::new(*x, *y), *radius, *strength, *damage, *by)
+        }
+        Action::Restore { snapshot } => sim.restore(snapshot)?,
+        Action::Reload => sim.reload()?,
+        Action::Ticks { count } => sim.run_ticks(*count),
+    }
+    Ok(())
+}
@@ -0,0 +1,315 @@
+//! Rollback: every peer runs ahead on what it knows and fixes the past when the other player's inputs arrive.
+//!
+//! Missing inputs of the other player are predicted to be nothing, which is right on nearly every frame.
+//! When an input shows up for a frame that already ran, the simulation goes back to the state before that frame
+//! and runs again up to the present. Local inputs take effect after `input_delay` frames, usually none or one.
+//!
+//! A frame that runs again brings about its events again, so the events of a frame only reach `poll_events`
+//! once the frame is final, when the other player's inputs for it are in.
+
+use serde_derive::{Serialize, Deserialize};
+
+use std::collections::BTreeMap;
+
+use crate::events::GameEvent;
+use crate::net::{play_frame, Input, Message, Peer, Received, Transport, INPUT_HISTORY};
+use crate::simulation::{Checkpoint, Simulation};
+use crate::{warn, GameError};
+
+/// How a `RollbackSession` plays, both peers need the same.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RollbackConfig {
+    /// frames between making an input and it taking effect (default: `1`)
+    pub input_delay: Option<u64>,
+    /// frames a peer may run ahead of the other player's inputs before it waits (default: `8`)
+    pub max_rollback: Option<u64>,
+    /// frames between two checksum comparisons, `0` never compares (default: `30`)
+    pub checksum_interval: Option<u64>,
+    /// frames between two checkpoints, each rebuilds the whole world, a rollback runs again from the last one before it (default: `4`)
+    pub checkpoint_interval: Option<u64>,
+}
+
+/// What the UI may want to show about the connection.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RollbackStatus {
+    /// the next frame to run, `None` until both peers are in
+    pub frame: Option<u64>,
+    /// the first frame the other player's inputs are missing for, everything before it is final
+    pub confirmed: u64,
+    /// stalled, `max_rollback` frames ahead of the other player
+    pub waiting: bool,
+    /// times a late input sent the simulation back
+    pub rollbacks: u64,
+    /// the last frame on which the peers disagreed
+    pub desync: Option<u64>,
+}
+
+/// One peer of a two player rollback match. Player 0 hosts: its state is the one both start from.
+///
+/// A restored world doesn't go on exactly like the one it was copied from, so the frames a rollback may start from
+/// run from a rebuilt world on both peers: every `checkpoint_interval` frames the whole world is rebuilt from a snapshot
+/// and an `Action::Reload` goes into the replay. A longer interval rebuilds less often and runs more frames again per rollback.
+pub struct RollbackSession<T: Transport> {
+    peer: Peer<T>,
+    max_rollback: u64,
+    checkpoint_interval: u64,
+    local_inputs: BTreeMap<u64, Vec<Input>>,
+    remote_inputs: BTreeMap<u64, Vec<Input>>,
+    /// the simulation before every `checkpoint_interval`th frame that may still have to run again, and before the last final one
+    checkpoints: BTreeMap<u64, Checkpoint>,
+    /// the earliest frame that ran without an input that since arrived
+    mispredicted: Option<u64>,
+    /// what the frames that may still run again brought about, held back until they are final
+    events: BTreeMap<u64, Vec<GameEvent>>,
+    /// frames before this one had their events delivered
+    delivered: u64,
+    /// `Simulation::checksum` after the frames due for a comparison, final or not
+    checksums: BTreeMap<u64, u32>,
+    /// frames before this one had their checksums sent
+    checked: u64,
+    rollbacks: u64,
+}
+
+impl<T: Transport> RollbackSession<T> {
+    /// `local` is the gorilla this peer plays, `0` or `1`.
+    pub fn new(transport: T, local: usize, config: &RollbackConfig) -> Result<Self, GameError> {
+        let peer = Peer::new(transport, local, config.input_delay.unwrap_or(1), config.checksum_interval.unwrap_or(30))?;
+        Ok(RollbackSession {
+            peer,
+            max_rollback: config.max_rollback.unwrap_or(8),
+            checkpoint_interval: config.checkpoint_interval.unwrap_or(4).max(1),
+            local_inputs: BTreeMap::new(),
+            remote_inputs: BTreeMap::new(),
+            checkpoints: BTreeMap::new(),
+            mispredicted: None,
+            events: BTreeMap::new(),
+            delivered: 0,
+            checksums: BTreeMap::new(),
+            checked: 0,
+            rollbacks: 0,
+        })
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.peer.transport
+    }
+
+    /// The player of this peer.
+    pub fn local(&self) -> usize {
+        self.peer.local
+    }
+
+    pub fn status(&self) -> RollbackStatus {
+        RollbackStatus {
+            frame: self.peer.frame,
+            confirmed: self.peer.next_remote,
+            waiting: self.peer.waiting,
+            rollbacks: self.rollbacks,
+            desync: self.peer.desync,
+        }
+    }
+
+    /// Something the local player did, it takes effect `input_delay` frames from now on both peers.
+    pub fn input(&mut self, input: Input) {
+        self.peer.input(input);
+    }
+
+    /// Handles whatever arrived, fixes the frames that ran on a wrong guess
+    /// and runs the frames `dt` seconds of real time are worth.
+    pub fn step(&mut self, sim: &mut Simulation, dt: f64) -> Result<(), GameError> {
+        let mut heard = false;
+        for message in self.peer.transport.receive() {
+            heard |= matches!(message, Message::Input { .. });
+            self.handle(sim, message)?;
+        }
+        let ts = sim.world().timestep();
+        let frame = match self.peer.frame {
+            Some(frame) => frame,
+            None => return Ok(()),
+        };
+
+        if let Some(from) = self.mispredicted.take() {
+            self.roll_back(sim, from, frame)?;
+        }
+
+        self.peer.elapse(dt, ts);
+        while self.peer.accumulator >= ts {
+            let frame = self.peer.frame.unwrap_or(0);
+            if frame >= self.peer.next_remote + self.max_rollback {
+                self.peer.waiting = true;
+                break;
+            }
+            self.schedule(frame);
+            self.run(sim, frame)?;
+            self.peer.accumulator -= ts;
+        }
+
+        // inputs may get lost on the way, they go again until the other side has them
+        self.peer.send_unacked();
+        if heard && self.peer.next_remote > 0 {
+            self.peer.transport.send(&Message::Ack { frame: self.peer.next_remote - 1 });
+        }
+        self.confirm(sim);
+        Ok(())
+    }
+
+    /// Keeps the local inputs once they have a frame, they go out with the unacknowledged ones.
+    fn schedule(&mut self, frame: u64) {
+        for (frame, inputs) in self.peer.schedule(frame) {
+            self.local_inputs.insert(frame, inputs);
+        }
+    }
+
+    /// Runs `frame`, with what is known about the other player's inputs by now.
+    /// Both peers take checkpoints on the same frames, they change how the simulation goes on.
+    fn run(&mut self, sim: &mut Simulation, frame: u64) -> Result<(), GameError> {
+        if frame.is_multiple_of(self.checkpoint_interval) || self.checkpoints.is_empty() {
+            self.checkpoints.insert(frame, sim.checkpoint()?);
+        }
+        self.play(sim, frame);
+        Ok(())
+    }
+
+    fn play(&mut self, sim: &mut Simulation, frame: u64) {
+        let polled = sim.pending_events();
+        let mut inputs = [None, None];
+        inputs[self.peer.local] = self.local_inputs.get(&frame);
+        inputs[self.peer.remote()] = self.remote_inputs.get(&frame);
+        play_frame(sim, inputs);
+        self.peer.frame = Some(frame + 1);
+        let events = sim.split_events(polled);
+        if frame >= self.delivered {
+            self.events.insert(frame, events);
+        }
+
+        if self.peer.checksum_interval > 0 && frame.is_multiple_of(self.peer.checksum_interval) {
+            self.checksums.insert(frame, sim.checksum());
+        }
+    }
+
+    /// Goes back to the last checkpoint before `from` and runs every frame from there up to `to` again.
+    fn roll_back(&mut self, sim: &mut Simulation, from: u64, to: u64) -> Result<(), GameError> {
+        let (start, checkpoint) = match self.checkpoints.range(..=from).next_back() {
+            Some((&start, checkpoint)) => (start, checkpoint),
+            None => {
+                warn!("cannot go back to frame {}, it is gone", from);
+                return Ok(());
+            }
+        };
+        // where the checkpoint left the simulation the first time, a checkpoint of it wouldn't be bit for bit the same
+        sim.rewind(checkpoint)?;
+        self.rollbacks += 1;
+        self.play(sim, start);
+        for frame in start + 1..to {
+            self.run(sim, frame)?;
+        }
+        Ok(())
+    }
+
+    /// Sends the checksums of the frames that are final now and forgets what no rollback can need anymore.
+    fn confirm(&mut self, sim: &mut Simulation) {
+        let next_remote = self.peer.next_remote;
+        let last = u64::min(next_remote, self.peer.frame.unwrap_or(0));
+        while self.checked < last {
+            let frame = self.checked;
+            self.checked += 1;
+            if let Some(checksum) = self.checksums.remove(&frame) {
+                if self.peer.checksum(frame, checksum) {
+                    self.resync_or_warn(sim);
+                }
+            }
+        }
+        while self.delivered < last {
+            if let Some(events) = self.events.remove(&self.delivered) {
+                sim.push_events(events);
+            }
+            self.delivered += 1;
+        }
+
+        // a late input can need the first unconfirmed frame again, which runs again from the last checkpoint before it
+        let oldest = self.checkpoints.range(..=next_remote).next_back().map_or(next_remote, |(&frame, _)| frame);
+        self.checkpoints = self.checkpoints.split_off(&oldest);
+        self.checksums = self.checksums.split_off(&next_remote);
+        let forgotten = next_remote.saturating_sub(INPUT_HISTORY);
+        self.local_inputs = self.local_inputs.split_off(&forgotten);
+        self.remote_inputs = self.remote_inputs.split_off(&forgotten);
+    }
+
+    fn handle(&mut self, sim: &mut Simulation, message: Message) -> Result<(), GameError> {
+        match self.peer.receive(message)? {
+            Received::Nothing => {}
+            Received::Resync => {
+                if self.peer.frame.is_none() {
+                    self.resync(sim)?;
+                } else {
+                    self.resync_or_warn(sim);
+                }
+            }
+            Received::Inputs { frame, inputs } => {
+                if frame < self.peer.next_remote || self.remote_inputs.contains_key(&frame) {
+                    // heard it before
+                    return Ok(());
+                }
+                // nothing is what was predicted
+                let ran = self.peer.frame.is_some_and(|next| frame < next);
+                if ran && !inputs.is_empty() {
+                    self.mispredicted = Some(self.mispredicted.map_or(frame, |earliest| u64::min(earliest, frame)));
+                }
+                self.remote_inputs.insert(frame, inputs);
+                while self.remote_inputs.contains_key(&self.peer.next_remote) {
+                    self.peer.next_remote += 1;
+                }
+            }
+            Received::Resynced { frame, epoch, snapshot } => {
+                let now = self.peer.frame.unwrap_or(frame);
+                sim.restore(&snapshot)?;
+                self.go_on_from(frame, epoch);
+                // catch up to where we were, the host does the same
+                for frame in frame..now {
+                    self.schedule(frame);
+                    self.run(sim, frame)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Player 0 only: makes the last state both agree on the one both go on from, and runs again from there.
+    /// It restores that state as well, a restored world has to run against a restored world.
+    fn resync(&mut self, sim: &mut Simulation) -> Result<(), GameError> {
+        let next_remote = self.peer.next_remote;
+        let (from, snapshot) = match self.peer.frame {
+            Some(now) if next_remote < now => (next_remote, self.checkpoints.get(&next_remote).map(|checkpoint| checkpoint.snapshot.clone())),
+            Some(now) => (now, None),
+            None => (0, None),
+        };
+        let snapshot = snapshot.unwrap_or_else(|| sim.snapshot());
+        let now = self.peer.frame.unwrap_or(from);
+        sim.restore(&snapshot)?;
+        let epoch = self.peer.resync(from, snapshot);
+        self.go_on_from(from, epoch);
+        for frame in from..now {
+            self.run(sim, frame)?;
+        }
+        Ok(())
+    }
+
+    fn resync_or_warn(&mut self, sim: &mut Simulation) {
+        if let Err(err) = self.resync(sim) {
+            warn!("cannot resync: {}", err);
+        }
+    }
+
+    fn go_on_from(&mut self, frame: u64, epoch: u32) {
+        self.peer.go_on_from(frame, epoch);
+        while self.remote_inputs.contains_key(&self.peer.next_remote) {
+            self.peer.next_remote += 1;
+        }
+        self.checked = frame;
+        self.mispredicted = None;
+        // these frames run again
+        self.events.split_off(&frame);
+        self.checkpoints.clear();
+        self.checksums.clear();
+    }
+}
@@ -1,9 +1,6 @@
 #![allow(unused_imports)]
+#[cfg(feature = "web")]
 use wasm_bindgen::prelude::*;
-use wasm_bindgen::JsCast;
-
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
-use web_sys::{EventTarget, KeyboardEvent};
 
 use serde_derive::{Serialize, Deserialize};
 
@@ -13,7 +10,9 @@ use ncollide2d::world::CollisionObjectHandle;
 use nphysics2d::object::{BodyHandle, Material};
 use nphysics2d::volumetric::Volumetric;
 
-use crate::{debug, SceneConfig};
+use crate::error::{ConfigError, GameError};
+use crate::util::Rng;
+use crate::{debug, BuildingConfig, SceneConfig};
 
 type World = nphysics2d::world::World<f64>;
 type Isometry2 = nalgebra::Isometry2<f64>;
@@ -27,8 +26,8 @@ pub struct Sprite {
     pub size: Vector2<f64>,
 }
 
-#[wasm_bindgen]
-#[derive(Default, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "web", wasm_bindgen)]
+#[derive(Default, Clone, Serialize, Deserialize, Debug)]
 pub struct BananaConfig {
     pub w: f64,
     pub h: f64,
@@ -37,6 +36,14 @@ pub struct BananaConfig {
     pub ttl: f64,
     pub stamina: Option<f64>,
     pub cost: f64,
+    /// damage per unit of momentum on impact with a gorilla (default: `100`)
+    pub damage: Option<f64>,
+    /// explosive only: bricks within this distance are damaged (default: `0.6`)
+    pub blast_radius: Option<f64>,
+    /// explosive only: impulse in N·s at the center of the blast, falling off to zero at its edge (default: `0.05`)
+    pub blast_strength: Option<f64>,
+    /// explosive only: damage to bricks at the center of the blast, falling off like the impulse (default: `0.5`)
+    pub blast_damage: Option<f64>,
 }
 pub struct Banana {
     pub shape: ShapeHandle,
@@ -47,6 +54,14 @@ pub struct Banana {
     pub ttl: f64,
     pub stamina: f64,
     pub explosive: bool,
+    pub blast_radius: f64,
+    pub blast_strength: f64,
+    pub blast_damage: f64,
+    pub damage: f64,
+    /// index of the gorilla who threw it
+    pub owner: Option<usize>,
+    /// seconds since it was thrown
+    pub age: f64,
 }
 
 impl Banana {
@@ -62,8 +77,17 @@ impl Banana {
         let ttl = config.ttl;
         let stamina = config.stamina.unwrap_or(0.05);
         let explosive = config.explosive;
+        let damage = config.damage.unwrap_or(100.0);
+
+        let blast_radius = config.blast_radius.unwrap_or(0.6);
+        let blast_strength = config.blast_strength.unwrap_or(0.05);
+        let blast_damage = config.blast_damage.unwrap_or(0.5);
 
-        Banana { shape, body, collision_object, sprite, uid, ttl, explosive, stamina }
+        Banana {
+            shape, body, collision_object, sprite, uid, ttl, explosive, blast_radius, blast_strength, blast_damage, stamina, damage,
+            owner: None,
+            age: 0.0,
+        }
     }
 }
 
@@ -72,6 +96,8 @@ pub struct Gorilla {
     pub body: BodyHandle,
     pub collision_object: CollisionObjectHandle,
     pub time_to_next_shot: f64,
+    pub health: f64,
+    pub max_health: f64,
 }
 
 impl Gorilla {
@@ -81,8 +107,13 @@ impl Gorilla {
         let body = world.add_rigid_body(pos, shape.inertia(config.inertia), shape.center_of_mass());
         let collision_object = world.add_collider(0.0, shape.clone(), body, Isometry2::identity(), Material::default());
         let time_to_next_shot = 0.;
+        let health = config.health.unwrap_or(100.0);
+
+        Gorilla { shape, body, collision_object, time_to_next_shot, health, max_health: health }
+    }
 
-        Gorilla { shape, body, collision_object, time_to_next_shot }
+    pub fn is_alive(&self) -> bool {
+        self.health > 0.0
     }
 
 
@@ -95,10 +126,15 @@ pub struct Brick {
     pub uid: usize,
     pub ttl: Option<f64>,
     pub fill_style: String,
+    /// what is left of `strength`, the brick breaks at zero
+    pub hit_points: f64,
+    pub strength: f64,
+    /// the gorilla whose banana dealt the final blow
+    pub broken_by: Option<usize>,
 }
 
 impl Brick {
-    pub fn new(world: &mut World, transform: Isometry2, radx: f64, rady: f64, margin: f64, fill_style: &str) -> Brick {
+    pub fn new(world: &mut World, transform: Isometry2, radx: f64, rady: f64, margin: f64, fill_style: &str, strength: f64) -> Brick {
         let shape = ShapeHandle::new(Cuboid::new(Vector2::new(radx, rady)));
         let body = world.add_rigid_body(transform, shape.inertia(0.1), shape.center_of_mass());
         let collision_object = world.add_collider(
@@ -110,14 +146,87 @@ impl Brick {
         );
         let uid = collision_object.uid();
         let ttl: Option<f64> = None;
-        Brick { shape, body, collision_object, uid, ttl, fill_style: fill_style.into() }
+        Brick { shape, body, collision_object, uid, ttl, fill_style: fill_style.into(), hit_points: strength, strength, broken_by: None }
     }
 
-    pub fn from_vector(world: &mut World, vector: Vector2<f64>, radx: f64, rady: f64, margin: f64, fill_style: &str) -> Self {
+    pub fn from_vector(world: &mut World, vector: Vector2<f64>, radx: f64, rady: f64, margin: f64, fill_style: &str, strength: f64) -> Self {
         let pos = Isometry2::new(vector, 0.0);
-        Brick::new(world, pos, radx, rady, margin, fill_style)
+        Brick::new(world, pos, radx, rady, margin, fill_style, strength)
+    }
+
+    /// Takes `damage` off the hit points and breaks the brick once they are gone,
+    /// `by` is the gorilla who caused it, if any.
+    pub fn damage(&mut self, damage: f64, by: Option<usize>) {
+        if self.is_broken() {
+            return;
+        }
+        self.hit_points = f64::max(self.hit_points - damage, 0.0);
+        if self.hit_points <= 0.0 {
+            self.ttl = Some(0.01);
+            self.broken_by = by;
+        }
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.hit_points <= 0.0 && self.ttl.is_some()
+    }
+
+    /// How worn the brick looks, from `0` for untouched to `1` for about to break.
+    pub fn wear(&self) -> f64 {
+        if self.strength > 0.0 { 1.0 - self.hit_points / self.strength } else { 0.0 }
+    }
+
+}
+
+/// Axis aligned extent of something in the scene, `top < bottom` since y points down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub left: f64,
+    pub right: f64,
+    pub top: f64,
+    pub bottom: f64,
+}
+
+impl Bounds {
+    pub fn around(x: f64, y: f64, radx: f64, rady: f64) -> Self {
+        Bounds { left: x - radx, right: x + radx, top: y - rady, bottom: y + rady }
     }
 
+    /// how far the two overlap horizontally, negative if there is a gap
+    pub fn overlap_x(&self, other: &Bounds) -> f64 {
+        f64::min(self.right, other.right) - f64::max(self.left, other.left)
+    }
+
+    pub fn intersects(&self, other: &Bounds) -> bool {
+        self.left < other.right && other.left < self.right
+            && self.top < other.bottom && other.top < self.bottom
+    }
+}
+
+pub fn ground_bounds(cfg: &SceneConfig) -> Bounds {
+    Bounds::around(
+        cfg.ground_x.unwrap_or(0.),
+        cfg.ground_y.unwrap_or(0.),
+        cfg.ground_radx.unwrap_or(1.),
+        cfg.ground_rady.unwrap_or(1.),
+    )
+}
+
+/// Where `make_building` will put the bricks of `building`.
+pub fn building_bounds(building: &BuildingConfig, cfg: &SceneConfig) -> Bounds {
+    let margin = cfg.margin.unwrap_or(0.);
+    let w = cfg.box_radx.unwrap_or(1.) + margin;
+    let h = cfg.box_rady.unwrap_or(1.) + margin;
+    let ground_top = ground_bounds(cfg).top;
+
+    let half_width = building.w as f64 * w + w * 0.5;
+    Bounds {
+        left: building.x - half_width,
+        right: building.x + half_width,
+        // the roof sits on top of `h` rows, the lowest row is centered on the ground surface
+        top: ground_top - h * (2. * building.h as f64) - h,
+        bottom: ground_top + h,
+    }
 }
 
 pub fn make_ground(world: &mut World, cfg: &SceneConfig) -> CollisionObjectHandle {
@@ -130,7 +239,6 @@ pub fn make_ground(world: &mut World, cfg: &SceneConfig) -> CollisionObjectHandl
     let x = cfg.ground_x.unwrap_or(0.);
     let y = cfg.ground_y.unwrap_or(0.);
     let pos = Isometry2::new(Vector2::new(x, y), zero());
-    debug!("make_ground {:?}", (margin, radius_x, radius_y, radius, pos));
 
     world.add_collider(
         margin,
@@ -141,7 +249,7 @@ pub fn make_ground(world: &mut World, cfg: &SceneConfig) -> CollisionObjectHandl
     )
 }
 
-pub fn make_building(world: &mut World, center: f64, cols: usize, rows: usize, fill_style: &str, cfg: &SceneConfig) -> Vec<Brick> {
+pub fn make_building(world: &mut World, center: f64, cols: usize, rows: usize, fill_style: &str, strength: f64, cfg: &SceneConfig) -> Vec<Brick> {
 
     let margin = cfg.margin.unwrap_or(0.);
     let radx = cfg.box_radx.unwrap_or(1.);
@@ -183,15 +291,15 @@ pub fn make_building(world: &mut World, center: f64, cols: usize, rows: usize, f
 
             // left corner brick
             if yi % 2 == 0 && xi == 0 {
-                bricks.push(Brick::from_vector(world, left_corner_pos, radx * 0.5, rady, margin, fill_style));
+                bricks.push(Brick::from_vector(world, left_corner_pos, radx * 0.5, rady, margin, fill_style, strength));
             }
 
             // normal brick
-            bricks.push(Brick::from_vector(world, row_pos, radx, rady, margin, fill_style));
+            bricks.push(Brick::from_vector(world, row_pos, radx, rady, margin, fill_style, strength));
 
             // right corner brick
-            if yi % 2 == 1 && xi == cols-1 {
-                bricks.push(Brick::from_vector(world, right_corner_pos, radx * 0.5, rady, margin, fill_style));
+            if yi % 2 == 1 && xi + 1 == cols {
+                bricks.push(Brick::from_vector(world, right_corner_pos, radx * 0.5, rady, margin, fill_style, strength));
             }
 
         }
@@ -210,8 +318,136 @@ pub fn make_building(world: &mut World, center: f64, cols: usize, rows: usize, f
             radx * cols as f64 + radx * 0.5,
             rady,
             margin,
-            fill_style
+            fill_style,
+            strength
         )
     );
     bricks
 }
+
+/// Parameters for `generate_skyline`, sizes are in bricks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SkylineConfig {
+    pub seed: u64,
+    pub buildings: usize,
+    pub min_height: usize,
+    pub max_height: usize,
+    pub min_width: usize,
+    pub max_width: usize,
+    /// horizontal space between two buildings
+    pub gap: f64,
+    pub palette: Vec<String>,
+}
+
+impl Default for SkylineConfig {
+    fn default() -> Self {
+        SkylineConfig {
+            seed: 0,
+            buildings: 8,
+            min_height: 10,
+            max_height: 40,
+            min_width: 3,
+            max_width: 7,
+            gap: 0.1,
+            palette: ["#04aaac", "#ac0204", "#acaaac", "#aa04ac", "#aaac04"].iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+impl SkylineConfig {
+    fn check(&self, template: &SceneConfig) -> Result<(), GameError> {
+        let problem = |field: &str, message: String| {
+            Err(GameError::Config(ConfigError {
+                context: "generate_skyline".into(),
+                level: None,
+                field: Some(field.into()),
+                expected: None,
+                message,
+            }))
+        };
+        if self.buildings < 2 {
+            return problem("buildings", format!("need at least 2 buildings for the players, got {}", self.buildings));
+        }
+        if self.min_height == 0 || self.min_height > self.max_height {
+            return problem("min_height", format!("needs 0 < min_height <= max_height, got {}..{}", self.min_height, self.max_height));
+        }
+        if self.min_width == 0 || self.min_width > self.max_width {
+            return problem("min_width", format!("needs 0 < min_width <= max_width, got {}..{}", self.min_width, self.max_width));
+        }
+        // NaN fails both
+        if !self.gap.is_finite() || self.gap < 0.0 {
+            return problem("gap", format!("must not be negative, got {}", self.gap));
+        }
+        if self.palette.is_empty() {
+            return problem("palette", "needs at least one color".into());
+        }
+
+        let widest = self.buildings as f64 * (building_width(self.max_width, template) + self.gap);
+        let ground = ground_bounds(template);
+        if widest > ground.right - ground.left {
+            return problem("buildings", format!("{} buildings of width {} may not fit on the ground", self.buildings, self.max_width));
+        }
+        Ok(())
+    }
+}
+
+fn building_width(w: usize, cfg: &SceneConfig) -> f64 {
+    let dummy = BuildingConfig { x: 0.0, w, h: 1, fill_style: String::new(), strength: None };
+    let bounds = building_bounds(&dummy, cfg);
+    bounds.right - bounds.left
+}
+
+/// A random city: buildings side by side, centered on the ground, one player on a roof at each end.
+///
+/// Everything but buildings and player positions (ground, brick size, gravity, player size) is taken from `template`.
+/// The same seed and template always give the same city, and the city always passes `SceneConfig::validate`.
+pub fn generate_skyline(params: &SkylineConfig, template: &SceneConfig) -> Result<SceneConfig, GameError> {
+    params.check(template)?;
+    let mut rng = Rng::new(params.seed);
+
+    let mut buildings: Vec<BuildingConfig> = Vec::with_capacity(params.buildings);
+    for _ in 0..params.buildings {
+        let w = rng.range_usize(params.min_width, params.max_width);
+        let h = rng.range_usize(params.min_height, params.max_height);
+        // never paint two neighbours alike if we have the colors for it
+        let mut color = rng.range_usize(0, params.palette.len() - 1);
+        if params.palette.len() > 1 && buildings.last().is_some_and(|prev| prev.fill_style == params.palette[color]) {
+            color = (color + 1) % params.palette.len();
+        }
+        buildings.push(BuildingConfig { x: 0.0, w, h, fill_style: params.palette[color].clone(), strength: None });
+    }
+
+    let total: f64 = buildings.iter().map(|b| building_width(b.w, template)).sum::<f64>()
+        + params.gap * (params.buildings - 1) as f64;
+    let mut left = template.ground_x.unwrap_or(0.) - total * 0.5;
+    for building in &mut buildings {
+        let width = building_width(building.w, template);
+        building.x = left + width * 0.5;
+        left += width + params.gap;
+    }
+
+    // players stand on a random roof in the outer third on their side
+    let third = usize::max(1, params.buildings / 3);
+    let a = rng.range_usize(0, third - 1);
+    let b = params.buildings - 1 - rng.range_usize(0, third - 1);
+
+    let mut scene = template.clone();
+    scene.name = Some(format!("skyline-{}", params.seed));
+    scene.player_a = on_roof(&template.player_a, &buildings[a], template);
+    scene.player_b = on_roof(&template.player_b, &buildings[b], template);
+    scene.buildings = buildings;
+
+    scene.validate().map_err(|problems| GameError::InvalidScene { level: scene.name.clone(), problems })?;
+    Ok(scene)
+}
+
+fn on_roof(player: &PlayerConfig, building: &BuildingConfig, cfg: &SceneConfig) -> PlayerConfig {
+    let roof = building_bounds(building, cfg).top;
+    PlayerConfig {
+        x: building.x,
+        // a little above the roof, so the margin can't make them touch before the first step
+        y: roof - player.rady - 0.05,
+        ..player.clone()
+    }
+}
@@ -0,0 +1,1063 @@
+#![allow(unused_imports)]
+use nalgebra::{Vector2, zero};
+use ncollide2d::events::ContactEvent;
+use ncollide2d::narrow_phase::ContactManifoldGenerator;
+use ncollide2d::world::CollisionObjectHandle;
+use nphysics2d::algebra::Force2;
+use nphysics2d::object::{BodyHandle, BodyStatus};
+
+use serde_derive::Serialize;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::checksum::{EntityChecksums, Fnv};
+use crate::debris::{self, Fragment};
+use crate::entities::{Entities, EntityId, Kind, Physical};
+use crate::match_state::{Match, MatchState, MatchStatus};
+use crate::replay::{Action, Mark, Replay};
+use crate::shapes::{self, Banana, Brick, Gorilla};
+use crate::snapshot::{BananaState, BrickState, FragmentState, GorillaState, Saved, Snapshot};
+use crate::util::Rng;
+use crate::wind::Wind;
+use crate::{debug, warn, GameConfig, GameError, GameEvent, IntegrationParameters, Point, SceneConfig, Shot};
+
+pub type World = nphysics2d::world::World<f64>;
+type Isometry2 = nalgebra::Isometry2<f64>;
+
+/// explosive bananas hit this much harder
+const EXPLOSIVE_DAMAGE_FACTOR: f64 = 2.0;
+/// a fresh banana can't hurt its thrower, it usually spawns touching them
+const SELF_HIT_GRACE: f64 = 0.25;
+/// bodies slower than this (m/s and rad/s) count as resting when deciding if a turn is over
+const SETTLED_SPEED: f64 = 0.05;
+/// slower impacts (m/s) don't damage bricks, or buildings would crumble while settling
+const MIN_IMPACT_SPEED: f64 = 1.0;
+
+/// One side of a contact.
+type Contact = (CollisionObjectHandle, EntityId);
+
+/// Sizes of everything the simulation and its world keep track of, to spot leaks.
+///
+/// Should return to the same values after every `reset` of the same scene.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DebugCounts {
+    pub colliders: usize,
+    /// rigid bodies in the world, the static ground isn't one
+    pub bodies: usize,
+    pub gorillas: usize,
+    pub bricks: usize,
+    pub bananas: usize,
+    pub debris: usize,
+    /// entries in the collider to entity map, the ground included
+    pub tracked_colliders: usize,
+    /// bodies with a remembered position for interpolation
+    pub remembered_bodies: usize,
+}
+
+/// The headless part of the game: physics world, entities and gameplay rules.
+///
+/// Knows nothing about canvases or the DOM, so it runs natively as well as in the browser.
+/// The wasm `Game` in `web.rs` wraps it and only adds rendering.
+pub struct Simulation {
+    objects: Entities,
+    world: World,
+    /// simulated time not yet consumed by a whole physics tick
+    accumulator: f64,
+    max_substeps: usize,
+    /// body positions before the latest tick, for render interpolation
+    previous_positions: HashMap<BodyHandle, Isometry2>,
+    /// body velocities before the latest tick, contacts are already resolved after it
+    previous_velocities: HashMap<BodyHandle, Vector2<f64>>,
+    /// not yet polled by the frontend
+    events: Vec<GameEvent>,
+    /// kept to set up a fresh `World` on every `clear_scene`
+    integration_parameters: Option<IntegrationParameters<f64>>,
+    /// the scene `reset` rebuilds
+    scene: Option<SceneConfig>,
+    /// the only source of randomness, so equal inputs give equal matches
+    rng: Rng,
+    seed: u64,
+    game_match: Match,
+    wind: Wind,
+    /// every input since `new`, enough to play it all again, if `recording`
+    replay: Replay,
+    recording: bool,
+    /// ticks since `new`
+    ticks: u64,
+    entity_checksum_interval: u64,
+}
+
+/// A simulation as it was before a rollback frame, see `Simulation::checkpoint`.
+pub(crate) struct Checkpoint {
+    pub snapshot: Snapshot,
+    ticks: u64,
+    replay: Mark,
+}
+
+impl Simulation {
+    pub fn new(conf: GameConfig) -> Simulation {
+        debug!("game config: {:?}", conf);
+        Simulation::build(conf)
+    }
+
+    /// A copy of the world in `snapshot` to try things out in, it logs nothing and records nothing.
+    pub(crate) fn scratch(conf: &GameConfig, snapshot: &Snapshot) -> Result<Simulation, GameError> {
+        let mut copy = Simulation::build(conf.clone());
+        copy.recording = false;
+        copy.load(snapshot)?;
+        Ok(copy)
+    }
+
+    /// Turns every brick and fragment that `moves` turns down into a static body, it still blocks but no longer moves.
+    /// The solver skips whatever can't move, so a copy that only cares about part of the world runs much faster.
+    pub(crate) fn freeze(&mut self, moves: impl Fn(Vector2<f64>) -> bool) {
+        let bodies = self.objects.bricks.items().iter().map(|brick| brick.body)
+            .chain(self.objects.debris.items().iter().map(|fragment| fragment.body));
+        for body in bodies {
+            if let Some(rb) = self.world.rigid_body_mut(body) {
+                if !moves(rb.position().translation.vector) {
+                    rb.set_status(BodyStatus::Static);
+                }
+            }
+        }
+    }
+
+    fn build(conf: GameConfig) -> Simulation {
+        let replay = Replay::new(conf.clone());
+        Simulation {
+            objects: Entities::default(),
+            world: new_world(&conf.integration_parameters),
+            accumulator: 0.0,
+            max_substeps: conf.max_substeps.unwrap_or(5),
+            previous_positions: Default::default(),
+            previous_velocities: Default::default(),
+            events: Vec::new(),
+            integration_parameters: conf.integration_parameters,
+            scene: None,
+            rng: Rng::new(conf.seed.unwrap_or(0)),
+            seed: conf.seed.unwrap_or(0),
+            game_match: Match::new(conf.rules.unwrap_or_default()),
+            wind: Wind::default(),
+            replay,
+            recording: conf.record.unwrap_or(false),
+            ticks: 0,
+            entity_checksum_interval: conf.entity_checksum_interval.unwrap_or(60),
+        }
+    }
+
+    /// The config this simulation was made from and everything done to it since,
+    /// nothing but the config unless `record` is on.
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    fn record(&mut self, action: Action) {
+        if self.recording {
+            self.replay.push(action);
+        }
+    }
+
+    /// Physics ticks since the simulation was created.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Restarts the random number generator from `seed`.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.record(Action::SetSeed { seed });
+        self.seed = seed;
+        self.rng = Rng::new(seed);
+    }
+
+    /// The seed last passed to `set_seed` or the `GameConfig`.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Draws a seed for something random outside the simulation, e.g. a level, so it still follows from the simulation's seed.
+    pub fn roll_seed(&mut self) -> u64 {
+        self.record(Action::RollSeed);
+        self.rng.next_u64()
+    }
+
+    pub fn game_match(&self) -> &Match {
+        &self.game_match
+    }
+
+    pub fn match_status(&self) -> MatchStatus {
+        self.game_match.status()
+    }
+
+    /// Starts a match on a freshly rebuilt scene, does nothing while one is running.
+    pub fn start_match(&mut self) -> Result<(), GameError> {
+        self.record(Action::StartMatch);
+        if self.game_match.start() {
+            self.rebuild()?;
+        }
+        Ok(())
+    }
+
+    /// While paused `step` does nothing at all.
+    pub fn pause(&mut self) {
+        self.record(Action::Pause);
+        self.game_match.pause();
+    }
+
+    pub fn resume(&mut self) {
+        self.record(Action::Resume);
+        self.game_match.resume();
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.record(Action::TogglePause);
+        self.game_match.toggle_pause();
+    }
+
+    /// Wind acceleration on bananas right now, in m/s².
+    pub fn wind(&self) -> Vector2<f64> {
+        self.wind.current()
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// Every entity of the scene, to look them up by the ids in `GameEvent`s.
+    pub fn entities(&self) -> &Entities {
+        &self.objects
+    }
+
+    pub fn gorillas(&self) -> &[Gorilla] {
+        self.objects.gorillas.items()
+    }
+
+    pub fn bricks(&self) -> &[Brick] {
+        self.objects.bricks.items()
+    }
+
+    pub fn bananas(&self) -> &[Banana] {
+        self.objects.bananas.items()
+    }
+
+    pub fn debris(&self) -> &[Fragment] {
+        self.objects.debris.items()
+    }
+
+    pub fn pos_of(&self, body: BodyHandle) -> Vector2<f64> {
+        if let Some(body) = self.world.rigid_body(body) {
+            body.position().translation.vector
+        } else {
+            warn!("cannot resolve position of ...");
+            zero()
+        }
+    }
+
+    pub fn rot_of(&self, body: BodyHandle) -> f64 {
+        if let Some(body) = self.world.rigid_body(body) {
+            body.position().rotation.angle()
+        } else {
+            warn!("cannot resolve position of ...");
+            zero()
+        }
+    }
+
+    /// How far the simulation is between the last two ticks, in `[0, 1)`.
+    pub fn alpha(&self) -> f64 {
+        self.accumulator / self.world.timestep()
+    }
+
+    /// Position of `body` blended between the previous and the current tick by `alpha()`.
+    pub fn interpolated_position(&self, body: BodyHandle) -> Isometry2 {
+        let current = match self.world.rigid_body(body) {
+            Some(body) => body.position(),
+            None => {
+                warn!("cannot resolve position of ...");
+                return Isometry2::identity();
+            }
+        };
+        let previous = match self.previous_positions.get(&body) {
+            Some(previous) => previous,
+            None => return current,
+        };
+
+        let alpha = self.alpha();
+        let translation = previous.translation.vector.lerp(&current.translation.vector, alpha);
+        let rotation = previous.rotation * previous.rotation.rotation_to(&current.rotation).powf(alpha);
+        Isometry2::from_parts(translation.into(), rotation)
+    }
+
+    /// Replaces whatever scene is loaded with `scene_config`.
+    pub fn set_scene(&mut self, scene_config: &SceneConfig) -> Result<(), GameError> {
+        self.build_scene(scene_config)?;
+        self.record(Action::SetScene { scene: Box::new(scene_config.clone()) });
+        Ok(())
+    }
+
+    fn build_scene(&mut self, scene_config: &SceneConfig) -> Result<(), GameError> {
+        scene_config.validate().map_err(|problems| GameError::InvalidScene {
+            level: scene_config.name.clone(),
+            problems,
+        })?;
+
+        self.clear();
+        self.scene = Some(scene_config.clone());
+
+        let world = &mut self.world;
+
+        world.set_gravity(Vector2::new(0.0, scene_config.gravity.unwrap_or(9.81)));
+        // a `Random` wind changes with every round, which all go through here
+        self.wind = Wind::new(&scene_config.wind.clone().unwrap_or_default(), &mut self.rng);
+
+        let ground = shapes::make_ground(world, scene_config);
+        self.objects.add_ground(ground);
+
+        for building in &scene_config.buildings {
+            // let &BuildingConfig {x, w, h, fill_style } = building;
+            let strength = building.strength.unwrap_or(0.1);
+            let bricks = shapes::make_building(world, building.x, building.w, building.h, &building.fill_style, strength, scene_config);
+            for brick in bricks {
+                self.objects.add_brick(brick);
+            }
+        }
+
+        // added first, so a gorilla's position in `gorillas()` is its player index
+        for player in &[&scene_config.player_a, &scene_config.player_b] {
+            self.objects.add_gorilla(Gorilla::new(world, player));
+        }
+        Ok(())
+    }
+
+    /// Tears down every body, collider and entity of the current scene.
+    ///
+    /// nphysics has no way to empty a world, so we start over with a fresh one.
+    pub fn clear_scene(&mut self) {
+        self.record(Action::ClearScene);
+        self.clear();
+    }
+
+    fn clear(&mut self) {
+        self.world = new_world(&self.integration_parameters);
+        self.objects = Entities::default();
+        self.previous_positions.clear();
+        self.previous_velocities.clear();
+        self.accumulator = 0.0;
+        self.wind = Wind::default();
+    }
+
+    /// Rebuilds the last scene passed to `set_scene` from scratch.
+    pub fn reset(&mut self) -> Result<(), GameError> {
+        self.record(Action::Reset);
+        self.rebuild()
+    }
+
+    fn rebuild(&mut self) -> Result<(), GameError> {
+        // a copy, the scene has to survive a failed build
+        match self.scene.clone() {
+            Some(scene) => self.build_scene(&scene),
+            None => {
+                self.clear();
+                Ok(())
+            }
+        }
+    }
+
+    /// Copies everything that changes while the simulation runs, to save it or to `restore` it later.
+    pub fn snapshot(&self) -> Snapshot {
+        let world = &self.world;
+        Snapshot {
+            scene: self.scene.clone(),
+            seed: self.seed,
+            rng: self.rng.clone(),
+            accumulator: self.accumulator,
+            wind: self.wind.clone(),
+            game_match: self.game_match.clone(),
+            gorillas: Saved::capture(&self.objects.gorillas, |id, gorilla| GorillaState::of(world, id, gorilla)),
+            bricks: Saved::capture(&self.objects.bricks, |id, brick| BrickState::of(world, id, brick)),
+            bananas: Saved::capture(&self.objects.bananas, |id, banana| BananaState::of(world, id, banana)),
+            debris: Saved::capture(&self.objects.debris, |id, fragment| FragmentState::of(world, id, fragment)),
+        }
+    }
+
+    /// Rebuilds the world from `snapshot`, entities keep their ids.
+    ///
+    /// Events not yet polled stay queued, they happened before the restore and a resync must not swallow them.
+    /// On error the simulation is left as it was.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), GameError> {
+        self.load(snapshot)?;
+        if self.recording {
+            self.replay.push(Action::Restore { snapshot: Box::new(snapshot.clone()) });
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the world from its own snapshot, which it returns to `rewind` to later.
+    ///
+    /// A restored world doesn't go on exactly like the one it was copied from, contacts start over.
+    /// Rollback runs the frames it may go back to from a rebuilt world, so running them again comes out the same.
+    pub(crate) fn checkpoint(&mut self) -> Result<Checkpoint, GameError> {
+        let checkpoint = Checkpoint { snapshot: self.snapshot(), ticks: self.ticks, replay: self.replay.mark() };
+        self.load(&checkpoint.snapshot)?;
+        self.record(Action::Reload);
+        Ok(checkpoint)
+    }
+
+    /// Goes back to `checkpoint` as if nothing happened since, the replay included.
+    /// Events are left alone, rollback holds back those of frames that may run again.
+    pub(crate) fn rewind(&mut self, checkpoint: &Checkpoint) -> Result<(), GameError> {
+        self.load(&checkpoint.snapshot)?;
+        self.ticks = checkpoint.ticks;
+        self.replay.truncate(checkpoint.replay);
+        self.record(Action::Reload);
+        Ok(())
+    }
+
+    /// What `checkpoint` does, for replays.
+    pub(crate) fn reload(&mut self) -> Result<(), GameError> {
+        let snapshot = self.snapshot();
+        self.load(&snapshot)?;
+        self.record(Action::Reload);
+        Ok(())
+    }
+
+    fn load(&mut self, snapshot: &Snapshot) -> Result<(), GameError> {
+        if let Some(scene) = &snapshot.scene {
+            scene.validate().map_err(|problems| GameError::InvalidScene { level: scene.name.clone(), problems })?;
+        }
+        let scene = snapshot.scene.as_ref();
+        let margin = scene.and_then(|scene| scene.margin).unwrap_or(0.0);
+
+        let mut world = new_world(&self.integration_parameters);
+        // same order as `set_scene`, then whatever was spawned later
+        let ground = scene.map(|scene| {
+            world.set_gravity(Vector2::new(0.0, scene.gravity.unwrap_or(9.81)));
+            shapes::make_ground(&mut world, scene)
+        });
+        let bricks = snapshot.bricks.restore(Kind::Brick, |brick| brick.restore(&mut world, margin))?;
+        let gorillas = snapshot.gorillas.restore(Kind::Gorilla, |gorilla| gorilla.restore(&mut world))?;
+        let bananas = snapshot.bananas.restore(Kind::Banana, |banana| banana.restore(&mut world))?;
+        let debris = snapshot.debris.restore(Kind::Debris, |fragment| fragment.restore(&mut world, margin))?;
+
+        self.world = world;
+        self.objects = Entities::from_stores(ground, gorillas, bricks, bananas, debris);
+        self.previous_positions.clear();
+        self.previous_velocities.clear();
+        self.accumulator = snapshot.accumulator;
+        self.scene = snapshot.scene.clone();
+        self.seed = snapshot.seed;
+        self.rng = snapshot.rng.clone();
+        self.wind = snapshot.wind.clone();
+        self.game_match = snapshot.game_match.clone();
+        Ok(())
+    }
+
+    /// Advances the simulation by `dt` seconds of real time.
+    ///
+    /// Physics only ever advances in whole ticks of `world.timestep()`,
+    /// the remainder is carried over to the next call and exposed as `alpha()`.
+    /// A `dt` that is negative, infinite or NaN is ignored.
+    pub fn step(&mut self, dt: f64) {
+        if self.game_match.is_paused() {
+            return;
+        }
+        // it would stay in the accumulator for good
+        if !dt.is_finite() || dt < 0.0 {
+            warn!("ignoring a step of {} seconds", dt);
+            return;
+        }
+        let ts = self.world.timestep();
+        // anything beyond `max_substeps` ticks is dropped below anyway
+        self.accumulator += f64::min(dt, ts * self.max_substeps as f64);
+
+        let mut substeps = 0;
+        while self.accumulator >= ts {
+            if substeps == self.max_substeps {
+                // we can't keep up, drop the backlog instead of spiraling to death
+                self.accumulator %= ts;
+                break;
+            }
+            // a new round may rebuild the scene during the tick, which starts the accumulator over
+            self.accumulator -= ts;
+            self.tick(ts);
+            self.record(Action::Ticks { count: 1 });
+            substeps += 1;
+        }
+    }
+
+    /// Runs `count` physics ticks regardless of the time, for replays.
+    pub(crate) fn run_ticks(&mut self, count: u64) {
+        let ts = self.world.timestep();
+        for _ in 0..count {
+            self.tick(ts);
+        }
+        self.record(Action::Ticks { count });
+    }
+
+    fn tick(&mut self, ts: f64) {
+        for gorilla in self.objects.gorillas.items_mut() {
+            gorilla.time_to_next_shot -= ts;
+        }
+        for banana in self.objects.bananas.items_mut() {
+            banana.age += ts;
+        }
+
+        self.remember_bodies();
+        self.wind.advance(ts);
+        self.blow();
+        self.world.step();
+
+        self.collisions();
+        self.gc(ts);
+
+        let alive: Vec<bool> = self.objects.gorillas.items().iter().map(Gorilla::is_alive).collect();
+        let settled = self.is_settled();
+        if self.game_match.update(ts, &alive, settled) {
+            if let Err(err) = self.rebuild() {
+                // the scene was valid when it was set, it still is
+                warn!("cannot rebuild the scene for the next round: {}", err);
+            }
+        }
+
+        self.ticks += 1;
+        if self.recording {
+            self.record_checksums();
+        }
+    }
+
+    fn record_checksums(&mut self) {
+        let entities = self.entity_checksums();
+        self.replay.checksums.push(self.combine(&entities));
+        if self.entity_checksum_interval > 0 && self.ticks.is_multiple_of(self.entity_checksum_interval) {
+            let entities = entities.into_iter().map(|(_, checksum)| checksum).collect();
+            self.replay.entity_checksums.push(EntityChecksums { tick: self.ticks, entities });
+        }
+    }
+
+    /// Hash of every body and every bit of gameplay state, equal simulations have equal checksums.
+    ///
+    /// Leaves out what doesn't change how the simulation goes on, e.g. the time left over between ticks.
+    pub fn checksum(&self) -> u32 {
+        self.combine(&self.entity_checksums())
+    }
+
+    fn combine(&self, entities: &[(EntityId, u32)]) -> u32 {
+        let mut hash = Fnv::default();
+        self.rng.checksum(&mut hash);
+        self.wind.checksum(&mut hash);
+        self.game_match.checksum(&mut hash);
+        for &(_, checksum) in entities {
+            hash.write_u32(checksum);
+        }
+        hash.finish()
+    }
+
+    /// Checksum of every entity: gorillas, bricks, bananas, then debris, each oldest first.
+    pub fn entity_checksums(&self) -> Vec<(EntityId, u32)> {
+        let world = &self.world;
+        let gorillas = self.objects.gorillas.iter().map(|(id, gorilla)| {
+            let mut hash = body_checksum(world, id, gorilla.body);
+            hash.write_f64(gorilla.health);
+            hash.write_f64(gorilla.time_to_next_shot);
+            (id, hash.finish())
+        });
+        let bricks = self.objects.bricks.iter().map(|(id, brick)| {
+            let mut hash = body_checksum(world, id, brick.body);
+            hash.write_f64(brick.hit_points);
+            hash.write_opt_f64(brick.ttl);
+            hash.write_opt_usize(brick.broken_by);
+            (id, hash.finish())
+        });
+        let bananas = self.objects.bananas.iter().map(|(id, banana)| {
+            let mut hash = body_checksum(world, id, banana.body);
+            hash.write_f64(banana.ttl);
+            hash.write_f64(banana.age);
+            hash.write(&[banana.explosive as u8]);
+            hash.write_opt_usize(banana.owner);
+            (id, hash.finish())
+        });
+        let debris = self.objects.debris.iter().map(|(id, fragment)| {
+            let mut hash = body_checksum(world, id, fragment.body);
+            hash.write_f64(fragment.ttl);
+            (id, hash.finish())
+        });
+        gorillas.chain(bricks).chain(bananas).chain(debris).collect()
+    }
+
+    /// Pushes bananas and, if the level wants it, loose bricks. Forces only last for one world step.
+    fn blow(&mut self) {
+        let (wind, debris) = (self.wind.current(), self.wind.on_debris());
+        let world = &mut self.world;
+        let mut push = |body: BodyHandle, acceleration: Vector2<f64>, only_loose: bool| {
+            if let Some(rb) = world.rigid_body_mut(body) {
+                // sleeping bricks are still part of a building, don't wake the whole city up
+                if rb.is_dynamic() && (rb.is_active() || !only_loose) {
+                    let mass = rb.inertia().linear;
+                    rb.apply_force(&Force2::linear(acceleration * mass));
+                }
+            }
+        };
+
+        if wind != zero::<Vector2<f64>>() {
+            for banana in self.objects.bananas.items() {
+                push(banana.body, wind, false);
+            }
+        }
+        if debris != zero::<Vector2<f64>>() {
+            for brick in self.objects.bricks.items() {
+                push(brick.body, debris, true);
+            }
+            for fragment in self.objects.debris.items() {
+                push(fragment.body, debris, true);
+            }
+        }
+    }
+
+    /// No bananas in the air and nothing moving anymore, debris doesn't count since it fades anyway.
+    pub fn is_settled(&self) -> bool {
+        let world = &self.world;
+        let resting = |body: BodyHandle| world.rigid_body(body).is_none_or(|rb| {
+            let velocity = rb.velocity();
+            velocity.linear.norm() < SETTLED_SPEED && velocity.angular.abs() < SETTLED_SPEED
+        });
+        self.objects.bananas.is_empty()
+            && self.objects.gorillas.items().iter().all(|g| resting(g.body))
+            && self.objects.bricks.items().iter().all(|b| resting(b.body))
+    }
+
+    fn remember_bodies(&mut self) {
+        let world = &self.world;
+        let bodies = self.objects.gorillas.items().iter().map(|g| g.body)
+            .chain(self.objects.bricks.items().iter().map(|b| b.body))
+            .chain(self.objects.bananas.items().iter().map(|b| b.body))
+            .chain(self.objects.debris.items().iter().map(|f| f.body));
+
+        self.previous_positions.clear();
+        self.previous_velocities.clear();
+        for body in bodies {
+            if let Some(rb) = world.rigid_body(body) {
+                self.previous_positions.insert(body, rb.position());
+                self.previous_velocities.insert(body, rb.velocity().linear);
+            }
+        }
+    }
+
+    fn gc(&mut self, dt: f64) {
+        self.gc_bananas(dt);
+        self.gc_debris(dt);
+        self.gc_bricks(dt);
+    }
+
+    /// The only way entities leave the scene: their body, its collider and all bookkeeping about them go together.
+    /// `clear_scene` doesn't need it, it throws away the whole world.
+    fn despawn<T: Physical>(&mut self, removed: &[(EntityId, T)]) {
+        self.objects.forget(removed);
+        let bodies: Vec<BodyHandle> = removed.iter().map(|(_, entity)| entity.body()).collect();
+        for body in &bodies {
+            self.previous_positions.remove(body);
+            self.previous_velocities.remove(body);
+        }
+        // takes the colliders attached to the bodies along, and wakes up whatever rested on them
+        self.world.remove_bodies(&bodies);
+    }
+
+    fn gc_bananas(&mut self, dt: f64) {
+        for banana in self.objects.bananas.items_mut() {
+            banana.ttl -= dt;
+        }
+        let garbage = self.objects.bananas.remove_where(|banana| banana.ttl < 0.0);
+        self.despawn(&garbage);
+    }
+
+    fn gc_bricks(&mut self, dt: f64) {
+        for brick in self.objects.bricks.items_mut() {
+            brick.ttl = brick.ttl.map(|ttl| ttl - dt);
+        }
+        let broken = self.objects.bricks.remove_where(|brick| brick.ttl.is_some_and(|ttl| ttl < 0.0));
+
+        for (id, brick) in &broken {
+            let pos = self.pos_of(brick.body);
+            self.events.push(GameEvent::BrickDestroyed { brick: *id, x: pos.x, y: pos.y, by: brick.broken_by });
+        }
+
+        // the fragments take over the body of the brick while it is still there
+        let scene = self.scene.as_ref();
+        if let Some(config) = scene.and_then(|scene| scene.debris.clone()) {
+            let margin = scene.and_then(|scene| scene.margin).unwrap_or(0.0);
+            let (per_brick, budget) = (config.split() * config.split(), config.budget());
+            // of all new fragments only the last `budget` would survive, the others aren't spawned at all
+            let mut skip = (broken.len() * per_brick).saturating_sub(budget);
+            let spawning = broken.len() * per_brick - skip;
+            let excess = (self.objects.debris.len() + spawning).saturating_sub(budget);
+            let dropped = self.objects.debris.remove_oldest(excess);
+            self.despawn(&dropped);
+
+            for (_, brick) in &broken {
+                let limit = per_brick.saturating_sub(skip);
+                skip = skip.saturating_sub(per_brick);
+                for fragment in debris::fracture(&mut self.world, brick, &config, margin, limit) {
+                    self.objects.add_fragment(fragment);
+                }
+            }
+        }
+        self.despawn(&broken);
+    }
+
+    fn gc_debris(&mut self, dt: f64) {
+        for fragment in self.objects.debris.items_mut() {
+            fragment.ttl -= dt;
+        }
+        let garbage = self.objects.debris.remove_where(|fragment| fragment.ttl < 0.0);
+        self.despawn(&garbage);
+    }
+
+    pub fn debug_counts(&self) -> DebugCounts {
+        // a body that lost its collider would not show up among the colliders' bodies
+        let bodies = self.objects.spawned_bodies().filter(|&body| self.world.rigid_body(body).is_some()).count();
+
+        DebugCounts {
+            colliders: self.world.colliders().count(),
+            bodies,
+            gorillas: self.objects.gorillas.len(),
+            bricks: self.objects.bricks.len(),
+            bananas: self.objects.bananas.len(),
+            debris: self.objects.debris.len(),
+            tracked_colliders: self.objects.tracked_colliders(),
+            remembered_bodies: self.previous_positions.len(),
+        }
+    }
+
+    fn collisions(&mut self) {
+        let mut gorilla_hits = Vec::new();
+        let mut brick_hits = Vec::new();
+        let mut impacts = Vec::new();
+
+        for event in self.world.contact_events().iter() {
+            if let ContactEvent::Started(collider1, collider2) = *event {
+                let (a, b) = match (self.objects.id_of(collider1), self.objects.id_of(collider2)) {
+                    (Some(id1), Some(id2)) => ((collider1, id1), (collider2, id2)),
+                    _ => continue,
+                };
+                if a.1.kind == Kind::Brick && b.1.kind == Kind::Brick {
+                    brick_hits.push((a, b));
+                    continue;
+                }
+                let (banana, other) = match (a.1.kind, b.1.kind) {
+                    (Kind::Banana, _) => (a, b),
+                    (_, Kind::Banana) => (b, a),
+                    _ => continue,
+                };
+
+                match other.1.kind {
+                    Kind::Brick => {
+                        if let Some(banana) = self.objects.bananas.get_mut(banana.1) {
+                            banana.ttl = f64::min(banana.stamina, banana.ttl);
+                        }
+                        brick_hits.push((banana, other));
+                    }
+                    Kind::Gorilla => gorilla_hits.push((banana, other)),
+                    Kind::Ground => {
+                        let point = self.contact_point(banana.0, other.0).unwrap_or_else(|| self.pos_of_collider(banana.0));
+                        self.events.push(GameEvent::BananaHitGround { banana: banana.1, x: point.x, y: point.y });
+                    }
+                    Kind::Banana | Kind::Debris => {}
+                }
+                impacts.push((banana, other.0));
+            }
+        }
+
+        for (banana, gorilla) in gorilla_hits {
+            self.banana_hits_gorilla(banana, gorilla);
+        }
+        for (a, b) in brick_hits {
+            self.brick_impact(a, b);
+        }
+        for (banana, other) in impacts {
+            self.detonate(banana, other);
+        }
+    }
+
+    /// Explosive bananas go off on their first impact with anything.
+    fn detonate(&mut self, (banana, id): Contact, other: CollisionObjectHandle) {
+        let center = self.contact_point(banana, other);
+        let banana = match self.objects.bananas.get_mut(id) {
+            Some(banana) if banana.explosive => banana,
+            _ => return,
+        };
+        let world = &self.world;
+        let center = center.unwrap_or_else(|| world.rigid_body(banana.body).map_or(zero(), |rb| rb.position().translation.vector));
+        // gone with the blast
+        banana.explosive = false;
+        banana.ttl = 0.0;
+        let (radius, strength, damage, by) = (banana.blast_radius, banana.blast_strength, banana.blast_damage, banana.owner);
+        self.blast(center, radius, strength, damage, by);
+    }
+
+    /// Both sides of an impact take the impulse it took to stop them relative to each other,
+    /// measured before the impact. Only bricks keep score of it.
+    ///
+    /// `b` is always a brick, `a` a brick or a banana.
+    fn brick_impact(&mut self, a: Contact, b: Contact) {
+        let (speed, impulse) = self.impact(a.0, b.0);
+        let banana = if a.1.kind == Kind::Banana { Some(a.1) } else { None };
+        let by = banana.and_then(|id| self.objects.bananas.get(id)).and_then(|banana| banana.owner);
+
+        if speed >= MIN_IMPACT_SPEED {
+            for &(_, id) in &[a, b] {
+                if let Some(brick) = self.objects.bricks.get_mut(id) {
+                    brick.damage(impulse, by);
+                }
+            }
+        }
+        if let Some(banana) = banana {
+            let point = self.contact_point(a.0, b.0).unwrap_or_else(|| self.pos_of_collider(a.0));
+            self.events.push(GameEvent::BananaHitBrick { banana, brick: b.1, x: point.x, y: point.y, impulse, by });
+        }
+    }
+
+    /// Relative speed of the two colliders before the latest tick and the impulse an inelastic impact at that speed takes.
+    fn impact(&self, a: CollisionObjectHandle, b: CollisionObjectHandle) -> (f64, f64) {
+        let world = &self.world;
+        let body = |collider: CollisionObjectHandle| world.collider(collider).map(|c| c.data().body());
+        let (body_a, body_b) = match (body(a), body(b)) {
+            (Some(body_a), Some(body_b)) => (body_a, body_b),
+            _ => return (0.0, 0.0),
+        };
+
+        let previous = &self.previous_velocities;
+        let velocity = |body: BodyHandle| previous.get(&body).cloned()
+            .or_else(|| world.rigid_body(body).map(|rb| rb.velocity().linear))
+            .unwrap_or_else(zero);
+        let mass = |body: BodyHandle| world.rigid_body(body).map_or(0.0, |rb| rb.inertia().linear);
+
+        let speed = (velocity(body_a) - velocity(body_b)).norm();
+        let (mass_a, mass_b) = (mass(body_a), mass(body_b));
+        if mass_a + mass_b <= 0.0 {
+            return (speed, 0.0);
+        }
+        (speed, mass_a * mass_b / (mass_a + mass_b) * speed)
+    }
+
+    fn pos_of_collider(&self, collider: CollisionObjectHandle) -> Vector2<f64> {
+        self.world.collider(collider).map_or(zero(), |c| c.position().translation.vector)
+    }
+
+    /// Midpoint of the deepest contact between the two colliders.
+    fn contact_point(&self, a: CollisionObjectHandle, b: CollisionObjectHandle) -> Option<Vector2<f64>> {
+        let algorithm = self.world.collision_world().contact_pair(a, b)?;
+        let mut manifolds = Vec::new();
+        algorithm.contacts(&mut manifolds);
+        let deepest = manifolds.iter()
+            .flat_map(|manifold| manifold.contacts())
+            .max_by(|x, y| x.contact.depth.partial_cmp(&y.contact.depth).unwrap_or(std::cmp::Ordering::Equal))?;
+        Some((deepest.contact.world1.coords + deepest.contact.world2.coords) * 0.5)
+    }
+
+    /// Damages every brick within `radius` of `center` and pushes everything in reach away from it,
+    /// impulse and damage fall off linearly from `strength` and `damage` at the center to zero at the edge.
+    pub fn explode(&mut self, center: Vector2<f64>, radius: f64, strength: f64, damage: f64, by: Option<usize>) {
+        self.record(Action::Explode { x: center.x, y: center.y, radius, strength, damage, by });
+        self.blast(center, radius, strength, damage, by);
+    }
+
+    fn blast(&mut self, center: Vector2<f64>, radius: f64, strength: f64, damage: f64, by: Option<usize>) {
+        let bodies: Vec<BodyHandle> = self.objects.bricks.items().iter().map(|b| b.body)
+            .chain(self.objects.gorillas.items().iter().map(|g| g.body))
+            .chain(self.objects.bananas.items().iter().filter(|b| b.ttl > 0.0).map(|b| b.body))
+            .chain(self.objects.debris.items().iter().map(|f| f.body))
+            .collect();
+
+        for body in bodies {
+            if let Some(rb) = self.world.rigid_body_mut(body) {
+                let offset = rb.position().translation.vector - center;
+                let distance = offset.norm();
+                if distance >= radius || !rb.is_dynamic() {
+                    continue;
+                }
+                // straight up if it went off right in the middle of something
+                let direction = if distance > 1e-9 { offset / distance } else { Vector2::new(0.0, -1.0) };
+                let impulse = direction * strength * (1.0 - distance / radius);
+                let velocity = rb.velocity().linear + impulse / rb.inertia().linear;
+                rb.activate();
+                rb.set_linear_velocity(velocity);
+            }
+        }
+
+        let world = &self.world;
+        for brick in self.objects.bricks.items_mut() {
+            let distance = world.rigid_body(brick.body).map(|rb| (rb.position().translation.vector - center).norm());
+            if let Some(distance) = distance.filter(|&distance| distance < radius) {
+                brick.damage(damage * (1.0 - distance / radius), by);
+            }
+        }
+
+        self.events.push(GameEvent::Explosion { x: center.x, y: center.y, radius, strength, by });
+    }
+
+    /// Damage scales with the banana's momentum relative to the gorilla, measured before the impact.
+    fn banana_hits_gorilla(&mut self, (banana, banana_id): Contact, (gorilla, gorilla_id): Contact) {
+        let point = self.contact_point(banana, gorilla).unwrap_or_else(|| self.pos_of_collider(banana));
+        let index = match self.objects.gorillas.position(gorilla_id) {
+            Some(index) => index,
+            None => return,
+        };
+        let banana = match self.objects.bananas.get_mut(banana_id) {
+            Some(banana) => banana,
+            None => return,
+        };
+        if banana.owner == Some(index) && banana.age < SELF_HIT_GRACE {
+            return;
+        }
+        banana.ttl = f64::min(banana.stamina, banana.ttl);
+
+        let world = &self.world;
+        let previous = &self.previous_velocities;
+        let velocity = |body: BodyHandle| previous.get(&body).cloned()
+            .or_else(|| world.rigid_body(body).map(|rb| rb.velocity().linear))
+            .unwrap_or_else(zero);
+        let mass = world.rigid_body(banana.body).map_or(0.0, |rb| rb.inertia().linear);
+
+        let gorilla = &mut self.objects.gorillas.items_mut()[index];
+        if !gorilla.is_alive() {
+            return;
+        }
+        let speed = (velocity(banana.body) - velocity(gorilla.body)).norm();
+        let explosive = if banana.explosive { EXPLOSIVE_DAMAGE_FACTOR } else { 1.0 };
+        let damage = f64::min(banana.damage * mass * speed * explosive, gorilla.health);
+
+        gorilla.health -= damage;
+        let by = banana.owner;
+        self.events.push(GameEvent::BananaHitGorilla {
+            banana: banana_id, gorilla: index, x: point.x, y: point.y, damage, health: gorilla.health, by,
+        });
+        if !gorilla.is_alive() {
+            gorilla.health = 0.0;
+            self.events.push(GameEvent::GorillaDied { gorilla: index, by });
+        }
+    }
+
+    /// Everything that happened since the last call, oldest first.
+    pub fn poll_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Events not yet polled.
+    pub(crate) fn pending_events(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Takes the events not yet polled after the first `at`, for rollback to hold them back until their frame is final.
+    pub(crate) fn split_events(&mut self, at: usize) -> Vec<GameEvent> {
+        self.events.split_off(at)
+    }
+
+    /// Queues `events` for `poll_events` after those already waiting.
+    pub(crate) fn push_events(&mut self, events: Vec<GameEvent>) {
+        self.events.extend(events);
+    }
+
+    fn gorilla_body(&self, index: usize) -> Result<BodyHandle, GameError> {
+        self.objects.gorillas.items().get(index)
+            .map(|gorilla| gorilla.body)
+            .ok_or(GameError::NoSuchGorilla { index, count: self.objects.gorillas.len() })
+    }
+
+    pub fn gorilla_pos(&self, index: usize) -> Result<Point, GameError> {
+        let pos = self.pos_of(self.gorilla_body(index)?);
+        Ok(Point { x: pos.x, y: pos.y })
+    }
+
+    pub fn move_gorilla(&mut self, idx: usize, point: Point) -> Result<(), GameError> {
+        debug!("moving gorilla {} to {:?}", idx, point);
+        let body = self.gorilla_body(idx)?;
+        self.record(Action::MoveGorilla { index: idx, point });
+        if let Some(gorilla) = self.world.rigid_body_mut(body) {
+            let r#move: Vector2<f64> = point.into();
+            let old_pos = gorilla.position();
+            let new_pos = Isometry2::new(
+                old_pos.translation.vector + r#move,
+                0f64
+            );
+            gorilla.set_position(new_pos);
+        }
+        Ok(())
+    }
+
+    pub fn shoot(&mut self, shot: &Shot) {
+        self.shoot_with_spin(shot, None);
+    }
+
+    /// `spin` overrides the random one, which is still drawn to keep the generator in step.
+    pub(crate) fn shoot_with_spin(&mut self, shot: &Shot, spin: Option<f64>) {
+        let spin = self.fire(shot, spin);
+        self.record(Action::Shoot { shot: shot.clone(), spin });
+    }
+
+    /// Returns the spin of the banana, `None` if the shot was rejected.
+    fn fire(&mut self, shot: &Shot, spin: Option<f64>) -> Option<f64> {
+        if !self.game_match.can_shoot(shot.gorilla_id) {
+            let event = match *self.game_match.state() {
+                MatchState::Countdown { remaining } => GameEvent::ShotRejectedNotStarted { gorilla: shot.gorilla_id, remaining },
+                _ => GameEvent::ShotRejectedOutOfTurn { gorilla: shot.gorilla_id },
+            };
+            self.events.push(event);
+            return None;
+        }
+        if let Some(gorilla) = self.objects.gorillas.items_mut().get_mut(shot.gorilla_id) {
+            if !gorilla.is_alive() {
+                return None;
+            } else if gorilla.time_to_next_shot > 0. {
+                let remaining = gorilla.time_to_next_shot;
+                self.events.push(GameEvent::ShotRejectedCooldown { gorilla: shot.gorilla_id, remaining });
+                return None;
+            } else {
+                gorilla.time_to_next_shot = shot.config.cost;
+            }
+        }
+
+        let mut banana = Banana::new(&mut self.world, &shot.config);
+        if shot.gorilla_id < self.objects.gorillas.len() {
+            banana.owner = Some(shot.gorilla_id);
+        }
+        let pos = Isometry2::new(Vector2::new(shot.x, shot.y), shot.rot);
+        let vel = Vector2::new(f64::cos(shot.rot), f64::sin(shot.rot)) * shot.power;
+        let rolled = self.rng.range_f64(-50.0, 50.0);
+        let spin = spin.unwrap_or(rolled);
+        if let Some(rb) = self.world.rigid_body_mut(banana.body) {
+            rb.set_position(pos);
+            rb.set_linear_velocity(vel);
+            rb.set_angular_velocity(spin);
+            let banana = self.objects.add_banana(banana);
+            self.events.push(GameEvent::ShotFired {
+                gorilla: shot.gorilla_id, banana, x: shot.x, y: shot.y, rot: shot.rot, power: shot.power,
+            });
+            self.game_match.shot_fired();
+        }
+        Some(spin)
+    }
+}
+
+/// Hash of the id and the motion of an entity, the caller adds whatever else it has.
+fn body_checksum(world: &World, id: EntityId, body: BodyHandle) -> Fnv {
+    let mut hash = Fnv::default();
+    hash.write_u32(id.kind as u32);
+    hash.write_u32(id.index);
+    hash.write_u32(id.generation);
+    if let Some(rb) = world.rigid_body(body) {
+        let (position, velocity) = (rb.position(), rb.velocity());
+        hash.write_f64(position.translation.vector.x);
+        hash.write_f64(position.translation.vector.y);
+        hash.write_f64(position.rotation.re);
+        hash.write_f64(position.rotation.im);
+        hash.write_f64(velocity.linear.x);
+        hash.write_f64(velocity.linear.y);
+        hash.write_f64(velocity.angular);
+    }
+    hash
+}
+
+fn new_world(integration_parameters: &Option<IntegrationParameters<f64>>) -> World {
+    let mut world = World::new();
+    if let Some(conf) = integration_parameters {
+        *world.integration_parameters_mut() = conf.clone().into();
+    }
+    world
+}
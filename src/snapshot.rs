@@ -0,0 +1,310 @@
+//! Plain data copy of a running `Simulation`, see `Simulation::snapshot` and `Simulation::restore`.
+//!
+//! Bodies are described by what they are and how they move, not by their handles in the physics world,
+//! so a snapshot can be saved, sent elsewhere and restored into a fresh world.
+
+use nalgebra::{Point2, Vector2};
+use ncollide2d::shape::Cuboid;
+use ncollide2d::world::CollisionObjectHandle;
+use nphysics2d::algebra::Inertia2;
+use nphysics2d::object::{BodyHandle, Material};
+use serde_derive::{Serialize, Deserialize};
+
+use crate::debris::Fragment;
+use crate::entities::{EntityId, Kind, Slots, Store};
+use crate::match_state::Match;
+use crate::shapes::{Banana, Brick, Gorilla, Sprite};
+use crate::util::Rng;
+use crate::wind::Wind;
+use crate::{GameError, SceneConfig};
+
+type World = nphysics2d::world::World<f64>;
+type Isometry2 = nalgebra::Isometry2<f64>;
+type ShapeHandle = ncollide2d::shape::ShapeHandle<f64>;
+
+/// Everything about a `Simulation` that changes while it runs.
+///
+/// Its config (timestep, substeps) is not part of it, restore into a simulation made from the same `GameConfig`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// the scene the next round will be rebuilt from, `None` if none was ever set
+    pub scene: Option<SceneConfig>,
+    pub seed: u64,
+    pub rng: Rng,
+    /// simulated time not yet consumed by a whole tick
+    pub accumulator: f64,
+    pub wind: Wind,
+    #[serde(rename = "match")]
+    pub game_match: Match,
+    pub gorillas: Saved<GorillaState>,
+    pub bricks: Saved<BrickState>,
+    pub bananas: Saved<BananaState>,
+    pub debris: Saved<FragmentState>,
+}
+
+/// The entities of one store, oldest first, and which ids the store has handed out.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Saved<T> {
+    pub items: Vec<T>,
+    pub slots: Slots,
+}
+
+impl<T> Saved<T> {
+    /// Entities without a body in `world` are left out.
+    pub fn capture<E, F>(store: &Store<E>, describe: F) -> Self
+    where
+        F: Fn(EntityId, &E) -> Option<T>,
+    {
+        Saved {
+            items: store.iter().filter_map(|(id, entity)| describe(id, entity)).collect(),
+            slots: store.slots(),
+        }
+    }
+
+    /// Builds every entity with `build` and puts them back under their old ids.
+    pub fn restore<E, F>(&self, kind: Kind, build: F) -> Result<Store<E>, GameError>
+    where
+        F: FnMut(&T) -> (EntityId, E),
+    {
+        let items = self.items.iter().map(build).collect();
+        Store::restore(kind, self.slots.clone(), items)
+            .ok_or_else(|| GameError::config("restore", format!("{:?} ids don't match their slots", kind)))
+    }
+}
+
+/// Where a body is, how it moves and what it weighs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BodyState {
+    pub x: f64,
+    pub y: f64,
+    /// radians
+    pub rot: f64,
+    pub vx: f64,
+    pub vy: f64,
+    /// rad/s
+    pub spin: f64,
+    pub mass: f64,
+    pub angular_inertia: f64,
+    /// asleep until something wakes it up
+    pub sleeping: bool,
+}
+
+impl BodyState {
+    /// `None` if `body` is not in `world`.
+    pub fn of(world: &World, body: BodyHandle) -> Option<Self> {
+        let rb = world.rigid_body(body)?;
+        let (position, velocity, inertia) = (rb.position(), rb.velocity(), rb.local_inertia());
+        Some(BodyState {
+            x: position.translation.vector.x,
+            y: position.translation.vector.y,
+            rot: position.rotation.angle(),
+            vx: velocity.linear.x,
+            vy: velocity.linear.y,
+            spin: velocity.angular,
+            mass: inertia.linear,
+            angular_inertia: inertia.angular,
+            sleeping: rb.is_dynamic() && !rb.is_active(),
+        })
+    }
+
+    /// Adds a body in this state to `world`, with a cuboid collider around it.
+    fn spawn(&self, world: &mut World, half_extents: Vector2<f64>, margin: f64, material: Material<f64>) -> (ShapeHandle, BodyHandle, CollisionObjectHandle) {
+        let shape = ShapeHandle::new(Cuboid::new(half_extents));
+        let position = Isometry2::new(Vector2::new(self.x, self.y), self.rot);
+        let body = world.add_rigid_body(position, Inertia2::new(self.mass, self.angular_inertia), Point2::origin());
+        let collision_object = world.add_collider(margin, shape.clone(), body, Isometry2::identity(), material);
+        if let Some(rb) = world.rigid_body_mut(body) {
+            rb.set_linear_velocity(Vector2::new(self.vx, self.vy));
+            rb.set_angular_velocity(self.spin);
+            if self.sleeping {
+                rb.deactivate();
+            }
+        }
+        (shape, body, collision_object)
+    }
+}
+
+fn half_extents(shape: &ShapeHandle) -> Vector2<f64> {
+    shape.as_shape::<Cuboid<f64>>().map_or(Vector2::new(0.0, 0.0), |cuboid| *cuboid.half_extents())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GorillaState {
+    pub id: EntityId,
+    pub body: BodyState,
+    pub radx: f64,
+    pub rady: f64,
+    pub health: f64,
+    pub max_health: f64,
+    /// cooldown, may be negative
+    pub time_to_next_shot: f64,
+}
+
+impl GorillaState {
+    pub fn of(world: &World, id: EntityId, gorilla: &Gorilla) -> Option<Self> {
+        let half_extents = half_extents(&gorilla.shape);
+        Some(GorillaState {
+            id,
+            body: BodyState::of(world, gorilla.body)?,
+            radx: half_extents.x,
+            rady: half_extents.y,
+            health: gorilla.health,
+            max_health: gorilla.max_health,
+            time_to_next_shot: gorilla.time_to_next_shot,
+        })
+    }
+
+    pub fn restore(&self, world: &mut World) -> (EntityId, Gorilla) {
+        let (shape, body, collision_object) = self.body.spawn(world, Vector2::new(self.radx, self.rady), 0.0, Material::default());
+        (self.id, Gorilla {
+            shape,
+            body,
+            collision_object,
+            time_to_next_shot: self.time_to_next_shot,
+            health: self.health,
+            max_health: self.max_health,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrickState {
+    pub id: EntityId,
+    pub body: BodyState,
+    pub radx: f64,
+    pub rady: f64,
+    pub fill_style: String,
+    pub hit_points: f64,
+    pub strength: f64,
+    /// only set once it is broken
+    pub ttl: Option<f64>,
+    pub broken_by: Option<usize>,
+}
+
+impl BrickState {
+    pub fn of(world: &World, id: EntityId, brick: &Brick) -> Option<Self> {
+        let half_extents = half_extents(&brick.shape);
+        Some(BrickState {
+            id,
+            body: BodyState::of(world, brick.body)?,
+            radx: half_extents.x,
+            rady: half_extents.y,
+            fill_style: brick.fill_style.clone(),
+            hit_points: brick.hit_points,
+            strength: brick.strength,
+            ttl: brick.ttl,
+            broken_by: brick.broken_by,
+        })
+    }
+
+    pub fn restore(&self, world: &mut World, margin: f64) -> (EntityId, Brick) {
+        let (shape, body, collision_object) = self.body.spawn(world, Vector2::new(self.radx, self.rady), margin, Material::new(0.0, 1.0));
+        (self.id, Brick {
+            shape,
+            body,
+            collision_object,
+            uid: collision_object.uid(),
+            ttl: self.ttl,
+            fill_style: self.fill_style.clone(),
+            hit_points: self.hit_points,
+            strength: self.strength,
+            broken_by: self.broken_by,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BananaState {
+    pub id: EntityId,
+    pub body: BodyState,
+    pub w: f64,
+    pub h: f64,
+    pub ttl: f64,
+    pub stamina: f64,
+    pub explosive: bool,
+    pub blast_radius: f64,
+    pub blast_strength: f64,
+    pub blast_damage: f64,
+    pub damage: f64,
+    pub owner: Option<usize>,
+    pub age: f64,
+}
+
+impl BananaState {
+    pub fn of(world: &World, id: EntityId, banana: &Banana) -> Option<Self> {
+        Some(BananaState {
+            id,
+            body: BodyState::of(world, banana.body)?,
+            w: banana.sprite.size.x,
+            h: banana.sprite.size.y,
+            ttl: banana.ttl,
+            stamina: banana.stamina,
+            explosive: banana.explosive,
+            blast_radius: banana.blast_radius,
+            blast_strength: banana.blast_strength,
+            blast_damage: banana.blast_damage,
+            damage: banana.damage,
+            owner: banana.owner,
+            age: banana.age,
+        })
+    }
+
+    pub fn restore(&self, world: &mut World) -> (EntityId, Banana) {
+        let size = Vector2::new(self.w, self.h);
+        let (shape, body, collision_object) = self.body.spawn(world, size * 0.5, 0.0, Material::default());
+        (self.id, Banana {
+            shape,
+            body,
+            collision_object,
+            sprite: Sprite { size },
+            uid: collision_object.uid(),
+            ttl: self.ttl,
+            stamina: self.stamina,
+            explosive: self.explosive,
+            blast_radius: self.blast_radius,
+            blast_strength: self.blast_strength,
+            blast_damage: self.blast_damage,
+            damage: self.damage,
+            owner: self.owner,
+            age: self.age,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FragmentState {
+    pub id: EntityId,
+    pub body: BodyState,
+    pub radx: f64,
+    pub rady: f64,
+    pub fill_style: String,
+    pub ttl: f64,
+    pub max_ttl: f64,
+}
+
+impl FragmentState {
+    pub fn of(world: &World, id: EntityId, fragment: &Fragment) -> Option<Self> {
+        let half_extents = half_extents(&fragment.shape);
+        Some(FragmentState {
+            id,
+            body: BodyState::of(world, fragment.body)?,
+            radx: half_extents.x,
+            rady: half_extents.y,
+            fill_style: fragment.fill_style.clone(),
+            ttl: fragment.ttl,
+            max_ttl: fragment.max_ttl,
+        })
+    }
+
+    pub fn restore(&self, world: &mut World, margin: f64) -> (EntityId, Fragment) {
+        let (shape, body, collision_object) = self.body.spawn(world, Vector2::new(self.radx, self.rady), margin, Material::new(0.0, 1.0));
+        (self.id, Fragment {
+            shape,
+            body,
+            collision_object,
+            ttl: self.ttl,
+            max_ttl: self.max_ttl,
+            fill_style: self.fill_style.clone(),
+        })
+    }
+}
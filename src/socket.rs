@@ -0,0 +1,49 @@
+//! Transport over whatever the JS side connects with, usually a `WebSocket`.
+
+use wasm_bindgen::prelude::*;
+
+use crate::net::{Message, Transport};
+use crate::{warn, GameError};
+
+#[wasm_bindgen]
+extern "C" {
+    /// Anything with a `send(text)` method, e.g. an open `WebSocket`.
+    pub type Socket;
+
+    #[wasm_bindgen(method)]
+    fn send(this: &Socket, data: &str);
+}
+
+/// Sends through a JS `Socket`, whatever it receives is handed in with `push`.
+pub struct SocketTransport {
+    socket: Socket,
+    inbox: Vec<Message>,
+}
+
+impl SocketTransport {
+    pub fn new(socket: Socket) -> Self {
+        SocketTransport { socket, inbox: Vec::new() }
+    }
+
+    /// A message as it came off the socket.
+    pub fn push(&mut self, text: &str) -> Result<(), GameError> {
+        let message = serde_json::from_str(text).map_err(|err| GameError::config("receive", err))?;
+        self.inbox.push(message);
+        Ok(())
+    }
+}
+
+impl Transport for SocketTransport {
+    fn send(&mut self, message: &Message) {
+        match serde_json::to_string(message) {
+            Ok(text) => self.socket.send(&text),
+            Err(err) => {
+                warn!("cannot send {:?}: {}", message, err);
+            }
+        }
+    }
+
+    fn receive(&mut self) -> Vec<Message> {
+        std::mem::take(&mut self.inbox)
+    }
+}
@@ -0,0 +1,65 @@
+use serde_derive::{Serialize, Deserialize};
+
+use crate::checksum::Fnv;
+
+/// Small seedable generator (SplitMix64), same seed, same numbers on every platform.
+///
+/// Not suitable for anything but gameplay.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub fn checksum(&self, hash: &mut Fnv) {
+        hash.write_u64(self.state);
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// uniform in `[0, 1)`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// uniform in `[low, high)`
+    pub fn range_f64(&mut self, low: f64, high: f64) -> f64 {
+        low + (high - low) * self.next_f64()
+    }
+
+    /// uniform in `low..=high`
+    pub fn range_usize(&mut self, low: usize, high: usize) -> usize {
+        debug_assert!(low <= high);
+        match ((high - low) as u64).checked_add(1) {
+            Some(span) => low + (self.next_u64() % span) as usize,
+            None => self.next_u64() as usize,
+        }
+    }
+}
+
+/// `#[serde(with = "crate::util::xy")]` for nalgebra vectors, as `[x, y]`.
+///
+/// nalgebra only serializes with a feature we don't pull in.
+pub mod xy {
+    use nalgebra::Vector2;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(vector: &Vector2<f64>, serializer: S) -> Result<S::Ok, S::Error> {
+        (vector.x, vector.y).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vector2<f64>, D::Error> {
+        let (x, y) = <(f64, f64)>::deserialize(deserializer)?;
+        Ok(Vector2::new(x, y))
+    }
+}
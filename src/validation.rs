@@ -0,0 +1,262 @@
+use serde_derive::Serialize;
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::shapes::{building_bounds, ground_bounds, Bounds};
+use crate::{PlayerConfig, SceneConfig};
+
+/// More bricks than this in one building is a typo, not a level.
+const MAX_BRICKS_PER_BUILDING: usize = 10_000;
+/// a brick breaks into at most this many fragments in each direction
+const MAX_DEBRIS_SPLIT: usize = 8;
+/// fragments alive at once, each is a body of its own
+const MAX_DEBRIS_BUDGET: usize = 1_000;
+
+/// Something in a `SceneConfig` that would build a broken world.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "problem")]
+pub enum SceneProblem {
+    /// a size or density that has to be strictly positive
+    NotPositive { field: String, value: f64 },
+    NotFinite { field: String },
+    EmptyBuilding { building: usize },
+    HugeBuilding { building: usize, bricks: usize },
+    /// a count that would spawn more bodies than the world can take
+    TooLarge { field: String, value: usize, max: usize },
+    BuildingsOverlap { a: usize, b: usize, overlap: f64 },
+    BuildingOffGround { building: usize },
+    PlayerInsideBuilding { player: String, building: usize },
+    PlayerInsideGround { player: String },
+    PlayersOverlap,
+}
+
+impl SceneProblem {
+    /// The number this problem is about, if it is about one.
+    fn field(&self) -> Option<&str> {
+        match self {
+            SceneProblem::NotPositive { field, .. } | SceneProblem::NotFinite { field } | SceneProblem::TooLarge { field, .. } => Some(field),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SceneProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::SceneProblem::*;
+        match self {
+            NotPositive { field, value } => write!(f, "{} must be positive, got {}", field, value),
+            NotFinite { field } => write!(f, "{} must be a finite number", field),
+            EmptyBuilding { building } => write!(f, "buildings[{}] needs at least one brick in each direction", building),
+            HugeBuilding { building, bricks } => write!(f, "buildings[{}] would have {} bricks, at most {} are allowed", building, bricks, MAX_BRICKS_PER_BUILDING),
+            TooLarge { field, value, max } => write!(f, "{} is {}, at most {} is allowed", field, value, max),
+            BuildingsOverlap { a, b, overlap } => write!(f, "buildings[{}] and buildings[{}] overlap by {:.2}", a, b, overlap),
+            BuildingOffGround { building } => write!(f, "buildings[{}] is not entirely on the ground", building),
+            PlayerInsideBuilding { player, building } => write!(f, "{} spawns inside buildings[{}]", player, building),
+            PlayerInsideGround { player } => write!(f, "{} spawns inside the ground", player),
+            PlayersOverlap => write!(f, "player_a and player_b spawn inside each other"),
+        }
+    }
+}
+
+impl SceneConfig {
+    /// Checks the scene for everything that would make `set_scene` build a broken world,
+    /// reporting all problems at once.
+    pub fn validate(&self) -> Result<(), Vec<SceneProblem>> {
+        let mut problems = Vec::new();
+
+        self.check_numbers(&mut problems);
+
+        // geometry from broken numbers is meaningless, only what is built from sane ones is checked
+        let broken: HashSet<String> = problems.iter().filter_map(SceneProblem::field).map(String::from).collect();
+        let sane = |field: &str| !broken.contains(field);
+
+        let ground = Some(ground_bounds(self)).filter(|_| ["ground_x", "ground_y", "ground_radx", "ground_rady"].iter().all(|field| sane(field)));
+        let bricks_sane = ground.is_some() && ["box_radx", "box_rady", "margin"].iter().all(|field| sane(field));
+        let buildings: Vec<Option<Bounds>> = self.buildings.iter().enumerate()
+            .map(|(i, building)| Some(building_bounds(building, self)).filter(|_| bricks_sane && sane(&format!("buildings[{}].x", i))))
+            .collect();
+        let player = |name: &str, p: &PlayerConfig| {
+            let fields_sane = ["x", "y", "radx", "rady"].iter().all(|field| sane(&format!("{}.{}", name, field)));
+            Some(Bounds::around(p.x, p.y, p.radx, p.rady)).filter(|_| fields_sane)
+        };
+        let players = [("player_a", player("player_a", &self.player_a)), ("player_b", player("player_b", &self.player_b))];
+
+        self.check_buildings(&mut problems, &buildings);
+        self.check_players(&mut problems, ground, &buildings, &players);
+
+        if problems.is_empty() { Ok(()) } else { Err(problems) }
+    }
+
+    fn check_numbers(&self, problems: &mut Vec<SceneProblem>) {
+        let mut finite = |field: &str, value: f64| {
+            if !value.is_finite() {
+                problems.push(SceneProblem::NotFinite { field: field.into() });
+                false
+            } else {
+                true
+            }
+        };
+
+        let optional = [
+            ("gravity", self.gravity),
+            ("margin", self.margin),
+            ("ground_x", self.ground_x),
+            ("ground_y", self.ground_y),
+        ];
+        for &(field, value) in optional.iter() {
+            if let Some(value) = value {
+                finite(field, value);
+            }
+        }
+
+        let mut positive = Vec::new();
+        for &(field, value) in [
+            ("box_radx", self.box_radx),
+            ("box_rady", self.box_rady),
+            ("ground_radx", self.ground_radx),
+            ("ground_rady", self.ground_rady),
+        ].iter() {
+            if let Some(value) = value {
+                if finite(field, value) {
+                    positive.push((field.to_string(), value));
+                }
+            }
+        }
+
+        let mut non_negative = Vec::new();
+        for (i, building) in self.buildings.iter().enumerate() {
+            finite(&format!("buildings[{}].x", i), building.x);
+            if let Some(strength) = building.strength {
+                let field = format!("buildings[{}].strength", i);
+                if finite(&field, strength) {
+                    non_negative.push((field, strength));
+                }
+            }
+        }
+
+        for &(name, player) in [("player_a", &self.player_a), ("player_b", &self.player_b)].iter() {
+            finite(&format!("{}.x", name), player.x);
+            finite(&format!("{}.y", name), player.y);
+            let health = player.health.map(|health| ("health", health));
+            for &(field, value) in [("radx", player.radx), ("rady", player.rady), ("inertia", player.inertia)].iter().chain(health.iter()) {
+                let field = format!("{}.{}", name, field);
+                if finite(&field, value) {
+                    positive.push((field, value));
+                }
+            }
+        }
+
+        let mut at_most = Vec::new();
+        if let Some(ref debris) = self.debris {
+            if let Some(ttl) = debris.ttl {
+                if finite("debris.ttl", ttl) {
+                    positive.push(("debris.ttl".into(), ttl));
+                }
+            }
+            for &(field, value, max) in [("debris.split", debris.split, MAX_DEBRIS_SPLIT), ("debris.budget", debris.budget, MAX_DEBRIS_BUDGET)].iter() {
+                if let Some(value) = value {
+                    at_most.push((field, value, max));
+                }
+            }
+        }
+
+        if let Some(ref wind) = self.wind {
+            for &(field, value) in [("wind.x", wind.x), ("wind.y", wind.y), ("wind.gust", wind.gust)].iter() {
+                if let Some(value) = value {
+                    finite(field, value);
+                }
+            }
+            if let Some(period) = wind.period {
+                if finite("wind.period", period) {
+                    positive.push(("wind.period".into(), period));
+                }
+            }
+            if let Some(debris) = wind.debris {
+                if finite("wind.debris", debris) && debris < 0.0 {
+                    problems.push(SceneProblem::NotPositive { field: "wind.debris".into(), value: debris });
+                }
+            }
+        }
+
+        if let Some(margin) = self.margin {
+            if margin < 0.0 {
+                problems.push(SceneProblem::NotPositive { field: "margin".into(), value: margin });
+            }
+        }
+        for (field, value) in positive {
+            if value <= 0.0 {
+                problems.push(SceneProblem::NotPositive { field, value });
+            }
+        }
+        for (field, value) in non_negative {
+            if value < 0.0 {
+                problems.push(SceneProblem::NotPositive { field, value });
+            }
+        }
+        for (field, value, max) in at_most {
+            if value > max {
+                problems.push(SceneProblem::TooLarge { field: field.into(), value, max });
+            }
+        }
+    }
+
+    /// `bounds` of each building, `None` for those built from broken numbers.
+    fn check_buildings(&self, problems: &mut Vec<SceneProblem>, bounds: &[Option<Bounds>]) {
+        // neighbours sharing a sliver are fine, the solver pushes them apart,
+        // anything more and bricks spawn inside each other
+        let tolerance = self.box_radx.unwrap_or(1.) * 0.5;
+        let ground = ground_bounds(self);
+
+        for (i, building) in self.buildings.iter().enumerate() {
+            if building.w == 0 || building.h == 0 {
+                problems.push(SceneProblem::EmptyBuilding { building: i });
+                continue;
+            }
+            let bricks = building.h.saturating_mul(building.w.saturating_add(1));
+            if bricks > MAX_BRICKS_PER_BUILDING {
+                problems.push(SceneProblem::HugeBuilding { building: i, bricks });
+            }
+            let here = match bounds[i] {
+                Some(here) => here,
+                None => continue,
+            };
+            if here.left < ground.left || here.right > ground.right {
+                problems.push(SceneProblem::BuildingOffGround { building: i });
+            }
+            for (j, there) in bounds.iter().enumerate().take(i) {
+                let overlap = match there {
+                    Some(there) => here.overlap_x(there),
+                    None => continue,
+                };
+                if overlap > tolerance {
+                    problems.push(SceneProblem::BuildingsOverlap { a: j, b: i, overlap });
+                }
+            }
+        }
+    }
+
+    /// Leaves out whatever of `ground`, `buildings` and `players` is `None`.
+    fn check_players(&self, problems: &mut Vec<SceneProblem>, ground: Option<Bounds>, buildings: &[Option<Bounds>], players: &[(&str, Option<Bounds>); 2]) {
+        for &(name, player) in players.iter() {
+            let player = match player {
+                Some(player) => player,
+                None => continue,
+            };
+            if ground.is_some_and(|ground| player.intersects(&ground)) {
+                problems.push(SceneProblem::PlayerInsideGround { player: name.into() });
+            }
+            for (i, building) in buildings.iter().enumerate() {
+                if building.is_some_and(|building| player.intersects(&building)) {
+                    problems.push(SceneProblem::PlayerInsideBuilding { player: name.into(), building: i });
+                }
+            }
+        }
+
+        if let (Some(a), Some(b)) = (players[0].1, players[1].1) {
+            if a.intersects(&b) {
+                problems.push(SceneProblem::PlayersOverlap);
+            }
+        }
+    }
+}
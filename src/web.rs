@@ -0,0 +1,604 @@
+#![allow(unused_imports)]
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, HtmlImageElement};
+
+use nalgebra::{Vector2, zero};
+use ncollide2d::shape::{Cuboid};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::ai::{Ai, AiConfig};
+use crate::dom_helpers;
+use crate::entities::EntityId;
+use crate::error::{parse_config, GameError};
+use crate::levels::{Level, LevelRegistry};
+use crate::lockstep::{LockstepConfig, LockstepSession};
+use crate::net::Input;
+use crate::replay::{Replay, ReplayPlayer};
+use crate::rollback::{RollbackConfig, RollbackSession};
+use crate::shapes::{self, Banana, SkylineConfig};
+use crate::simulation::Simulation;
+use crate::snapshot::Snapshot;
+use crate::socket::{Socket, SocketTransport};
+use crate::{debug, warn, GameConfig, Point, SceneConfig, Shot, ViewConfig};
+
+type ShapeHandle = ncollide2d::shape::ShapeHandle<f64>;
+
+impl From<GameError> for JsValue {
+    fn from(err: GameError) -> JsValue {
+        to_js("error", &err).unwrap_or_else(|_| JsValue::from(err.to_string()))
+    }
+}
+
+/// Goes through JSON text like the loopback transport, `undefined` comes out as `null`.
+fn parse<T: DeserializeOwned>(context: &str, raw: &JsValue) -> Result<T, GameError> {
+    let text = js_sys::JSON::stringify(raw)
+        .map_err(|err| GameError::config(context, format!("{:?}", err)))?
+        .as_string()
+        .unwrap_or_else(|| String::from("null"));
+    let value: Value = serde_json::from_str(&text).map_err(|err| GameError::config(context, err))?;
+    parse_config(context, value)
+}
+
+/// Plain JS object of `value`, through JSON text the other way.
+fn to_js<T: Serialize + ?Sized>(context: &str, value: &T) -> Result<JsValue, GameError> {
+    let text = serde_json::to_string(value).map_err(|err| GameError::serialize(context, err))?;
+    js_sys::JSON::parse(&text).map_err(|err| GameError::serialize(context, format!("{:?}", err)))
+}
+
+/// Canvas frontend around the headless `Simulation`.
+#[wasm_bindgen]
+pub struct Game {
+    canvas: HtmlCanvasElement,
+    sim: Simulation,
+    levels: LevelRegistry,
+    gorilla_png: HtmlImageElement,
+    /// while a replay plays, `step` follows it instead of the clock
+    playback: Option<ReplayPlayer>,
+    /// while connected, inputs go through the session and `step` runs at its pace
+    session: Option<Session>,
+    /// gorillas the computer plays, offline only
+    computer: Vec<Ai>,
+}
+
+/// A networked match, in whichever way `connect` or `connect_rollback` set it up.
+enum Session {
+    Lockstep(LockstepSession<SocketTransport>),
+    Rollback(RollbackSession<SocketTransport>),
+}
+
+impl Session {
+    fn local(&self) -> usize {
+        match self {
+            Session::Lockstep(session) => session.local(),
+            Session::Rollback(session) => session.local(),
+        }
+    }
+
+    fn input(&mut self, input: Input) {
+        match self {
+            Session::Lockstep(session) => session.input(input),
+            Session::Rollback(session) => session.input(input),
+        }
+    }
+
+    fn step(&mut self, sim: &mut Simulation, dt: f64) -> Result<(), GameError> {
+        match self {
+            Session::Lockstep(session) => session.step(sim, dt),
+            Session::Rollback(session) => session.step(sim, dt),
+        }
+    }
+
+    fn push(&mut self, text: &str) -> Result<(), GameError> {
+        match self {
+            Session::Lockstep(session) => session.transport_mut().push(text),
+            Session::Rollback(session) => session.transport_mut().push(text),
+        }
+    }
+
+    fn status(&self) -> Result<JsValue, GameError> {
+        match self {
+            Session::Lockstep(session) => to_js("network_status", &session.status()),
+            Session::Rollback(session) => to_js("network_status", &session.status()),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Game {
+    #[wasm_bindgen(constructor)]
+    pub fn new(canvas: HtmlCanvasElement, gorilla_png: HtmlImageElement, config: &JsValue) -> Result<Game, JsValue> {
+        let conf: GameConfig = parse("Game::new", config)?;
+
+        Ok(Game {
+            canvas,
+            sim: Simulation::new(conf),
+            levels: LevelRegistry::builtin()?,
+            gorilla_png,
+            playback: None,
+            session: None,
+            computer: Vec::new(),
+        })
+    }
+
+    fn size_of(&self, shape: &ShapeHandle) -> Vector2<f64> {
+        if let Some(cube) = shape.as_shape::<Cuboid<_>>() {
+            let size = cube.half_extents();
+            let (w, h) = (size.x, size.y);
+            Vector2::new( w * 2., h * 2. )
+        } else {
+            warn!("this object  is not a cube");
+            Vector2::new(0.4, 0.7)
+        }
+    }
+
+    pub fn set_scene(&mut self, raw_scene_config: &JsValue) -> Result<(), JsValue> {
+        let scene_config: SceneConfig = parse("set_scene", raw_scene_config)?;
+        self.sim.set_scene(&scene_config)?;
+        Ok(())
+    }
+
+    /// Names of all known levels, built-in ones first.
+    pub fn level_names(&self) -> Vec<JsValue> {
+        self.levels.names().into_iter().map(JsValue::from).collect()
+    }
+
+    pub fn load_level(&mut self, name: &str) -> Result<(), JsValue> {
+        let scene = self.levels.get(name)?;
+        self.sim.set_scene(scene)?;
+        Ok(())
+    }
+
+    /// Registers a level object in the level file format, returns its name.
+    pub fn add_level(&mut self, raw_level: &JsValue) -> Result<String, JsValue> {
+        let level = Level::from_value(parse("add_level", raw_level)?)?;
+        let name = level.name.clone();
+        self.levels.add(level);
+        Ok(name)
+    }
+
+    /// Generates a random city from `{ seed, buildings, min_height, .. }` and registers it as a level,
+    /// returns the level name for `load_level`.
+    pub fn generate_level(&mut self, raw_params: &JsValue) -> Result<String, JsValue> {
+        let mut params: Value = parse("generate_level", raw_params)?;
+        if let Some(params) = params.as_object_mut() {
+            // no seed given, roll one so the city still follows from the game seed
+            let sim = &mut self.sim;
+            params.entry("seed").or_insert_with(|| sim.roll_seed().into());
+        }
+        let params: SkylineConfig = parse_config("generate_level", params)?;
+        // physics constants come from the first built-in level, so a seed gives the same city everywhere
+        let template = &self.levels.levels()[0].scene;
+        let scene = shapes::generate_skyline(&params, template)?;
+        let name = scene.name.clone().unwrap_or_default();
+        self.levels.add(Level { name: name.clone(), scene });
+        Ok(name)
+    }
+
+    /// JS passes a `BigInt`, e.g. `game.set_seed(BigInt(Date.now()))`.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.sim.set_seed(seed);
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.sim.seed()
+    }
+
+    pub fn clear_scene(&mut self) {
+        self.sim.clear_scene();
+    }
+
+    /// Rebuilds the current scene, e.g. for the next round.
+    pub fn reset(&mut self) -> Result<(), JsValue> {
+        self.sim.reset()?;
+        Ok(())
+    }
+
+    pub fn render_scene(&self, raw_view_config: &JsValue) -> Result<(), JsValue> {
+        let view_config: ViewConfig = parse("render_scene", raw_view_config)?;
+
+        let zoom = view_config.zoom.unwrap_or(1.0);
+        let width = self.canvas.width() as f64;
+        let height = self.canvas.height() as f64;
+        let background = "#0402ac";
+        // let background = "#ffffff";
+
+        let ctx = dom_helpers::canvas_get_ctx_2d(&self.canvas);
+
+        // background
+        ctx.save();
+            ctx.set_fill_style_str(background);
+            ctx.fill_rect(0.0, 0.0, self.canvas.width().into(), self.canvas.height().into());
+        ctx.restore();
+
+        // foreground
+        ctx.save();
+            ctx.translate(0.5 * width, 0.5 * height)?;
+
+            ctx.rotate(view_config.rotation.unwrap_or(0.0))?;
+
+            ctx.translate( (-0.5 * width ) / zoom, (-0.5 * height) / zoom)?;
+
+            ctx.scale(zoom, zoom)?;
+
+            ctx.translate(view_config.x.unwrap_or(0.0), view_config.y.unwrap_or(0.0))?;
+
+            ctx.set_fill_style_str("#FFFF00");
+            ctx.fill_rect(-0.25, -4.25, 0.5, 0.5);
+
+            self.render_bricks(&ctx)?;
+            self.render_debris(&ctx)?;
+            self.render_players(&ctx)?;
+            self.render_bananas(&ctx)?;
+
+        ctx.restore();
+
+        Ok(())
+    }
+
+    fn render_bricks(&self, ctx: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+        for (id, brick) in self.sim.entities().bricks.iter() {
+            self.render_brick(ctx, id, brick)?;
+        }
+        Ok(())
+    }
+
+    fn render_brick(&self, ctx: &CanvasRenderingContext2d, id: EntityId, brick: &shapes::Brick) -> Result<(), JsValue> {
+        let position = self.sim.interpolated_position(brick.body);
+        let pos = position.translation.vector;
+        let size = self.size_of(&brick.shape);
+        let angle = position.rotation.angle();
+
+        ctx.begin_path();
+        ctx.save();
+        ctx.translate(pos.x , pos.y)?;
+        ctx.rotate(angle)?;
+
+        ctx.rect(-size.x * 0.5, - size.y * 0.5, size.x, size.y);
+        ctx.set_line_width(0.02);
+        ctx.stroke();
+        ctx.set_fill_style_str(&brick.fill_style);
+        ctx.fill();
+
+        let wear = brick.wear();
+        if wear > 0.0 {
+            // darker the closer it is to breaking
+            ctx.set_fill_style_str(&format!("rgba(0, 0, 0, {})", 0.5 * wear));
+            ctx.fill();
+        }
+        if wear > 0.25 {
+            self.render_cracks(ctx, id, size, wear);
+        }
+
+        ctx.restore();
+        Ok(())
+    }
+
+    /// A zigzag across the brick that reaches further the more worn it is,
+    /// its shape only depends on the brick's id so it doesn't flicker, not even across a restore.
+    fn render_cracks(&self, ctx: &CanvasRenderingContext2d, id: EntityId, size: Vector2<f64>, wear: f64) {
+        let (w, h) = (size.x * 0.5, size.y * 0.5);
+        let flip = if id.index.is_multiple_of(2) { 1.0 } else { -1.0 };
+        let steps = 4;
+
+        ctx.begin_path();
+        ctx.move_to(-w * flip, 0.0);
+        for i in 1..=steps {
+            let x = -w + 2.0 * w * wear * i as f64 / steps as f64;
+            let y = if i % 2 == 0 { -0.4 * h } else { 0.4 * h };
+            ctx.line_to(x * flip, y);
+        }
+        ctx.set_line_width(0.015);
+        ctx.stroke();
+    }
+
+    fn render_debris(&self, ctx: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+        for fragment in self.sim.debris() {
+            let position = self.sim.interpolated_position(fragment.body);
+            let pos = position.translation.vector;
+            let size = self.size_of(&fragment.shape);
+
+            ctx.save();
+            ctx.translate(pos.x, pos.y)?;
+            ctx.rotate(position.rotation.angle())?;
+            // fades out over the last half second
+            ctx.set_global_alpha(f64::min(1.0, fragment.ttl / f64::min(0.5, fragment.max_ttl)));
+            ctx.set_fill_style_str(&fragment.fill_style);
+            ctx.fill_rect(-size.x * 0.5, -size.y * 0.5, size.x, size.y);
+            ctx.restore();
+        }
+        Ok(())
+    }
+
+    fn render_bananas(&self, ctx: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+        for banana in self.sim.bananas() {
+            self.render_banana(ctx, banana)?;
+        }
+        Ok(())
+    }
+
+    fn render_banana(&self, ctx: &CanvasRenderingContext2d, banana: &Banana) -> Result<(), JsValue> {
+        let position = self.sim.interpolated_position(banana.body);
+        let pos = position.translation.vector;
+        let size = banana.sprite.size;
+        let angle = position.rotation.angle();
+
+        ctx.begin_path();
+        ctx.save();
+        ctx.translate(pos.x , pos.y)?;
+        ctx.rotate(angle)?;
+
+        ctx.rect(-size.x * 0.5, - size.y * 0.5, size.x, size.y);
+        ctx.set_line_width(0.02);
+        ctx.stroke();
+        ctx.set_fill_style_str("yellow");
+        ctx.fill_rect(-size.x * 0.5, - size.y * 0.5, size.x, size.y);
+        ctx.restore();
+        Ok(())
+    }
+
+    pub fn render_players(&self, ctx: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+        for gorilla in self.sim.gorillas() {
+            let position = self.sim.interpolated_position(gorilla.body);
+            let pos = position.translation.vector;
+            let size = self.size_of(&gorilla.shape);
+            let angle = position.rotation.angle();
+
+            ctx.save();
+            ctx.translate(pos.x , pos.y)?;
+            ctx.rotate(angle)?;
+
+            ctx.draw_image_with_html_image_element_and_dw_and_dh(&self.gorilla_png, -size.x * 0.5, -size.y * 0.5, size.x, size.y)?;
+
+            ctx.restore();
+
+            // health bar, not rotated with the gorilla
+            let health = gorilla.health / gorilla.max_health;
+            ctx.save();
+            ctx.translate(pos.x, pos.y - size.y * 0.5 - 0.15)?;
+            ctx.set_fill_style_str("red");
+            ctx.fill_rect(-0.3, 0.0, 0.6, 0.06);
+            ctx.set_fill_style_str("lime");
+            ctx.fill_rect(-0.3, 0.0, 0.6 * health, 0.06);
+            ctx.restore();
+        }
+        Ok(())
+    }
+
+    /// `dt` is the real time since the last frame in seconds
+    pub fn step(&mut self, dt: f64) {
+        match self.playback.as_mut() {
+            Some(player) => {
+                if let Err(err) = player.step(&mut self.sim, dt) {
+                    warn!("replay stopped: {}", err);
+                    self.playback = None;
+                } else if player.is_finished() {
+                    self.playback = None;
+                }
+            }
+            None => match self.session.as_mut() {
+                Some(session) => {
+                    if let Err(err) = session.step(&mut self.sim, dt) {
+                        warn!("disconnected: {}", err);
+                        self.session = None;
+                    }
+                }
+                None => {
+                    for ai in &mut self.computer {
+                        if let Some(shot) = ai.think(&self.sim, dt) {
+                            self.sim.shoot(&shot);
+                        }
+                    }
+                    self.sim.step(dt)
+                }
+            },
+        }
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.sim.alpha()
+    }
+
+    /// Ignored while a replay plays.
+    pub fn shoot(&mut self, raw_shot: &JsValue) -> Result<(), JsValue> {
+        if self.is_replaying() {
+            return Ok(());
+        }
+        let shot: Shot = parse("shoot", raw_shot)?;
+        match self.session.as_mut() {
+            Some(session) => session.input(Input::Shoot { shot }),
+            None => self.sim.shoot(&shot),
+        }
+        Ok(())
+    }
+
+    /// Starts a match on the current scene, scores start over.
+    pub fn start_match(&mut self) -> Result<(), JsValue> {
+        match self.session.as_mut() {
+            Some(session) => session.input(Input::StartMatch),
+            None => self.sim.start_match()?,
+        }
+        Ok(())
+    }
+
+    pub fn toggle_pause(&mut self) {
+        match self.session.as_mut() {
+            Some(session) => session.input(Input::TogglePause),
+            None => self.sim.toggle_pause(),
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if self.session.is_some() {
+            if !self.sim.game_match().is_paused() {
+                self.toggle_pause();
+            }
+            return;
+        }
+        self.sim.pause();
+    }
+
+    pub fn resume(&mut self) {
+        if self.session.is_some() {
+            if self.sim.game_match().is_paused() {
+                self.toggle_pause();
+            }
+            return;
+        }
+        self.sim.resume();
+    }
+
+    /// Lets the computer play `gorilla`, `config` is `{ difficulty: "Easy" | "Normal" | "Hard", weapons: [banana configs], seed }`.
+    /// It only plays while not connected.
+    pub fn play_computer(&mut self, gorilla: usize, raw_config: &JsValue) -> Result<(), JsValue> {
+        let config: AiConfig = parse("play_computer", raw_config)?;
+        let ai = Ai::new(gorilla, &config)?;
+        self.computer.retain(|ai| ai.gorilla() != gorilla);
+        self.computer.push(ai);
+        Ok(())
+    }
+
+    /// Hands `gorilla` back to a human.
+    pub fn stop_computer(&mut self, gorilla: usize) {
+        self.computer.retain(|ai| ai.gorilla() != gorilla);
+    }
+
+    /// Plays gorilla `player` against whoever is on the other end of `socket`, anything with `send(text)`,
+    /// e.g. an open `WebSocket`. Hand every message that arrives on it to `receive`.
+    ///
+    /// Player 0 hosts, the other side takes over its scene and match. `config` is `{ input_delay, checksum_interval }`.
+    pub fn connect(&mut self, socket: Socket, player: usize, raw_config: &JsValue) -> Result<(), JsValue> {
+        let config: LockstepConfig = parse("connect", raw_config)?;
+        self.session = Some(Session::Lockstep(LockstepSession::new(SocketTransport::new(socket), player, &config)?));
+        Ok(())
+    }
+
+    /// Like `connect`, but runs ahead on a guess of the other player's inputs and takes back what it got wrong,
+    /// so nobody waits for the network. `config` is `{ input_delay, max_rollback, checksum_interval, checkpoint_interval }`.
+    pub fn connect_rollback(&mut self, socket: Socket, player: usize, raw_config: &JsValue) -> Result<(), JsValue> {
+        let config: RollbackConfig = parse("connect_rollback", raw_config)?;
+        self.session = Some(Session::Rollback(RollbackSession::new(SocketTransport::new(socket), player, &config)?));
+        Ok(())
+    }
+
+    pub fn receive(&mut self, text: &str) -> Result<(), JsValue> {
+        if let Some(session) = self.session.as_mut() {
+            session.push(text)?;
+        }
+        Ok(())
+    }
+
+    /// The game goes on locally from where it is.
+    pub fn disconnect(&mut self) {
+        self.session = None;
+    }
+
+    /// `{ frame, waiting, desync }`, with `{ confirmed, rollbacks }` for rollback, or `null` when not connected.
+    pub fn network_status(&self) -> Result<JsValue, JsValue> {
+        match &self.session {
+            Some(session) => Ok(session.status()?),
+            None => Ok(JsValue::NULL),
+        }
+    }
+
+    /// `{ state: { state: "Countdown", remaining }, scores: [0, 1], round, rounds_to_win }`
+    pub fn match_status(&self) -> Result<JsValue, JsValue> {
+        Ok(to_js("match_status", &self.sim.match_status())?)
+    }
+
+    /// Current wind acceleration as `{ x, y }` in m/s², for the HUD.
+    pub fn wind(&self) -> Result<JsValue, JsValue> {
+        let wind = self.sim.wind();
+        let wind = Point { x: wind.x, y: wind.y };
+        Ok(to_js("wind", &wind)?)
+    }
+
+    /// Events since the last call, e.g. `{ event: "BananaHitGorilla", banana, gorilla, x, y, damage, health, by }`,
+    /// see `GameEvent` for all of them.
+    pub fn poll_events(&mut self) -> Result<JsValue, JsValue> {
+        let events = self.sim.poll_events();
+        Ok(to_js("poll_events", &events)?)
+    }
+
+    /// Entity and physics world sizes, e.g. `{ colliders, bodies, bricks, .. }`, to check for leaks.
+    pub fn debug_counts(&self) -> Result<JsValue, JsValue> {
+        let counts = self.sim.debug_counts();
+        Ok(to_js("debug_counts", &counts)?)
+    }
+
+    /// Everything needed to `restore` the game as it is now, plain JSON to keep as a save game or bug report.
+    pub fn snapshot(&self) -> Result<JsValue, JsValue> {
+        let snapshot = self.sim.snapshot();
+        Ok(to_js("snapshot", &snapshot)?)
+    }
+
+    /// Puts the game back to what `snapshot` returned, events not yet polled still come with the next `poll_events`.
+    pub fn restore(&mut self, raw_snapshot: &JsValue) -> Result<(), JsValue> {
+        let snapshot: Snapshot = parse("restore", raw_snapshot)?;
+        self.sim.restore(&snapshot)?;
+        Ok(())
+    }
+
+    /// Every input since the game was created, as `{ config, actions: [{ action: "Shoot", shot, spin }, ..] }`.
+    pub fn replay(&self) -> Result<JsValue, JsValue> {
+        Ok(to_js("replay", self.sim.replay())?)
+    }
+
+    /// Starts the game over from the config of `replay` and plays it back with the following calls to `step`,
+    /// shots and moves are ignored until it is over.
+    pub fn play_replay(&mut self, raw_replay: &JsValue) -> Result<(), JsValue> {
+        let replay: Replay = parse("play_replay", raw_replay)?;
+        let player = ReplayPlayer::new(replay);
+        self.sim = player.simulation();
+        self.playback = Some(player);
+        Ok(())
+    }
+
+    /// Plays `replay` on the side and checks it against the checksums it recorded,
+    /// `null` if it still plays the same or `{ tick, expected, actual, entity }` where it first went another way.
+    pub fn verify_replay(&self, raw_replay: &JsValue) -> Result<JsValue, JsValue> {
+        let replay: Replay = parse("verify_replay", raw_replay)?;
+        let divergence = replay.verify()?;
+        Ok(to_js("verify_replay", &divergence)?)
+    }
+
+    /// Hash of the whole game state after the latest tick, equal games have equal checksums.
+    pub fn checksum(&self) -> u32 {
+        self.sim.checksum()
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    /// Stops the replay where it is, the game goes on from there.
+    pub fn stop_replay(&mut self) {
+        self.playback = None;
+    }
+
+    pub fn gorilla_pos(&self, index: usize) -> Result<JsValue, JsValue> {
+        let pos = self.sim.gorilla_pos(index)?;
+        Ok(to_js("gorilla_pos", &pos)?)
+    }
+
+    /// Ignored while a replay plays.
+    pub fn move_gorilla(&mut self, idx: usize, raw_point: &JsValue) -> Result<(), JsValue> {
+        if self.is_replaying() {
+            return Ok(());
+        }
+        let point: Point = parse("move_gorilla", raw_point)?;
+        if let Some(session) = self.session.as_mut() {
+            // only your own
+            if idx == session.local() {
+                session.input(Input::MoveGorilla { point });
+            }
+            return Ok(());
+        }
+        self.sim.move_gorilla(idx, point)?;
+        Ok(())
+    }
+}
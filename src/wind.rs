@@ -0,0 +1,99 @@
+use nalgebra::{Vector2, zero};
+use serde_derive::{Serialize, Deserialize};
+
+use std::f64::consts::PI;
+
+use crate::checksum::Fnv;
+use crate::util::Rng;
+
+type Num = Option<f64>;
+
+/// Wind of a level, part of the `SceneConfig`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WindConfig {
+    /// (default: `Constant`)
+    pub mode: Option<WindMode>,
+    /// wind acceleration in m/s², for `Random` the largest value in either direction (default: `0`)
+    pub x: Num,
+    pub y: Num,
+    /// `Gusting` only: how far the wind swings around its base, `0.5` means ±50% (default: `0.5`)
+    pub gust: Num,
+    /// `Gusting` only: seconds from one gust to the next (default: `4`)
+    pub period: Num,
+    /// share of the wind that pushes loose bricks, `0` leaves them alone (default: `0`)
+    pub debris: Num,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum WindMode {
+    Constant,
+    Gusting,
+    /// a new constant wind every round, rolled from the simulation's seed
+    Random,
+}
+
+/// The wind blowing right now.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Wind {
+    #[serde(with = "crate::util::xy")]
+    base: Vector2<f64>,
+    gust: f64,
+    period: f64,
+    /// where in the gust cycle the round started
+    phase: f64,
+    time: f64,
+    debris: f64,
+}
+
+impl Default for Wind {
+    fn default() -> Self {
+        Wind { base: zero(), gust: 0.0, period: 1.0, phase: 0.0, time: 0.0, debris: 0.0 }
+    }
+}
+
+impl Wind {
+    /// Rolls whatever is random about `config`, call once per round.
+    pub fn new(config: &WindConfig, rng: &mut Rng) -> Self {
+        let (x, y) = (config.x.unwrap_or(0.0), config.y.unwrap_or(0.0));
+        let mode = config.mode.unwrap_or(WindMode::Constant);
+
+        let base = match mode {
+            WindMode::Random => Vector2::new(rng.range_f64(-x, x), rng.range_f64(-y, y)),
+            _ => Vector2::new(x, y),
+        };
+        let (gust, phase) = match mode {
+            WindMode::Gusting => (config.gust.unwrap_or(0.5), rng.range_f64(0.0, 2.0 * PI)),
+            _ => (0.0, 0.0),
+        };
+
+        Wind {
+            base,
+            gust,
+            period: config.period.unwrap_or(4.0),
+            phase,
+            time: 0.0,
+            debris: config.debris.unwrap_or(0.0),
+        }
+    }
+
+    pub fn advance(&mut self, dt: f64) {
+        self.time += dt;
+    }
+
+    pub fn checksum(&self, hash: &mut Fnv) {
+        for &value in &[self.base.x, self.base.y, self.gust, self.period, self.phase, self.time, self.debris] {
+            hash.write_f64(value);
+        }
+    }
+
+    /// Acceleration the wind gives bananas right now.
+    pub fn current(&self) -> Vector2<f64> {
+        let swing = self.gust * f64::sin(self.phase + 2.0 * PI * self.time / self.period);
+        self.base * (1.0 + swing)
+    }
+
+    /// Acceleration the wind gives loose bricks right now.
+    pub fn on_debris(&self) -> Vector2<f64> {
+        self.current() * self.debris
+    }
+}
@@ -0,0 +1,153 @@
+//! The computer player has to hit what it aims at, as well as its difficulty lets it.
+
+mod common;
+
+use common::{banana, scene, simulation, DT};
+use minimal::ai::{ballistic_power, Ai, AiConfig, MAX_TRIALS, TRIAL_HORIZON};
+use minimal::events::GameEvent;
+use minimal::levels::LevelRegistry;
+use minimal::{GameError, Shot, Simulation};
+use nalgebra::Vector2;
+use serde_json::{json, Value};
+
+/// `Shot` keeps its fields to itself.
+fn field(shot: &Shot, name: &str) -> Value {
+    serde_json::to_value(shot).unwrap()[name].clone()
+}
+
+fn computer(difficulty: &str, weapons: Vec<Value>) -> Ai {
+    let config: AiConfig = serde_json::from_value(json!({ "difficulty": difficulty, "weapons": weapons, "seed": 3 })).unwrap();
+    Ai::new(1, &config).unwrap()
+}
+
+/// Throws what `ai` comes up with and returns the damage it did to gorilla 0.
+fn throw(sim: &mut Simulation, ai: &mut Ai) -> f64 {
+    let mut shot = None;
+    for _ in 0..600 {
+        shot = ai.think(sim, DT);
+        if shot.is_some() {
+            break;
+        }
+        sim.step(DT);
+    }
+    sim.shoot(&shot.expect("the computer never threw"));
+    let mut damage = 0.0;
+    for _ in 0..360 {
+        sim.step(DT);
+        for event in sim.poll_events() {
+            if let GameEvent::BananaHitGorilla { gorilla: 0, damage: dealt, .. } = event {
+                damage += dealt;
+            }
+        }
+    }
+    damage
+}
+
+#[test]
+fn formula_hits_in_empty_space() {
+    let pull = Vector2::new(1.5, 9.81);
+    let offset = Vector2::new(-8.0, 1.0);
+    let rot = std::f64::consts::PI + 0.6;
+    let power = ballistic_power(offset, rot, pull).unwrap();
+    let velocity = Vector2::new(rot.cos(), rot.sin()) * power;
+    // time to get there sideways, then see where it is up and down
+    let t = {
+        let (a, b, c) = (0.5 * pull.x, velocity.x, -offset.x);
+        (-b - (b * b - 4.0 * a * c).sqrt()) / (2.0 * a)
+    };
+    let y = velocity.y * t + 0.5 * pull.y * t * t;
+    assert!((y - offset.y).abs() < 1e-9, "{} instead of {}", y, offset.y);
+
+    // straight down the wind can't get anywhere up
+    assert_eq!(ballistic_power(Vector2::new(0.0, -5.0), std::f64::consts::FRAC_PI_2, pull), None);
+}
+
+#[test]
+fn hard_computer_hits() {
+    let mut sim = simulation(scene());
+    let mut ai = computer("Hard", vec![banana(false, 5.0)]);
+    let aim = ai.aim(&sim).unwrap();
+    assert!(aim.damage > 0.0);
+    assert!(throw(&mut sim, &mut ai) > 0.0);
+}
+
+#[test]
+fn wind_is_accounted_for() {
+    let mut scene = scene();
+    scene["wind"] = json!({ "x": 3.0 });
+    let mut sim = simulation(scene);
+    let mut ai = computer("Hard", vec![banana(false, 5.0)]);
+    assert!(throw(&mut sim, &mut ai) > 0.0);
+}
+
+#[test]
+fn picks_the_weapon_that_hurts_most() {
+    let sim = simulation(scene());
+    let mut feeble = banana(false, 5.0);
+    feeble["damage"] = json!(1.0);
+    let mut heavy = banana(false, 5.0);
+    heavy["damage"] = json!(300.0);
+    let ai = computer("Hard", vec![feeble, heavy]);
+    let aim = ai.aim(&sim).unwrap();
+    assert_eq!(field(&aim.shot, "config")["damage"], json!(300.0));
+}
+
+#[test]
+fn easier_computers_take_longer_and_shake() {
+    let sim = simulation(scene());
+    let reaction = |difficulty: &str| {
+        let mut ai = computer(difficulty, vec![banana(false, 5.0)]);
+        (1..).find(|_| ai.think(&sim, DT).is_some()).unwrap()
+    };
+    assert!(reaction("Easy") > reaction("Normal"));
+    assert!(reaction("Normal") > reaction("Hard"));
+
+    let mut easy = computer("Easy", vec![banana(false, 5.0)]);
+    let aim = easy.aim(&sim).unwrap();
+    let thrown = (0..).find_map(|_| easy.think(&sim, DT)).unwrap();
+    let rot = |shot: &Shot| field(shot, "rot").as_f64().unwrap();
+    let power = |shot: &Shot| field(shot, "power").as_f64().unwrap();
+    assert_ne!((rot(&thrown), power(&thrown)), (rot(&aim.shot), power(&aim.shot)));
+    assert!((rot(&thrown) - rot(&aim.shot)).abs() <= 0.12);
+}
+
+#[test]
+fn aiming_at_a_big_level_takes_bounded_work() {
+    let scene = LevelRegistry::builtin().unwrap().get("valley").unwrap().clone();
+    let mut sim = Simulation::new(common::game_config());
+    sim.set_scene(&scene).unwrap();
+    // what index.js hands the computer, the light banana lives for 40 s
+    let weapons = vec![
+        json!({ "w": 0.2, "h": 0.08, "inertia": 0.8, "stamina": 15, "ttl": 40, "cost": 0.01, "explosive": false }),
+        json!({ "w": 0.3, "h": 0.1, "inertia": 1, "ttl": 10, "cost": 0.3, "explosive": true }),
+        json!({ "w": 0.6, "h": 0.2, "inertia": 20, "stamina": 0.1, "ttl": 3.5, "cost": 0.3, "explosive": false }),
+    ];
+    let ai = computer("Normal", weapons);
+    let aim = ai.aim(&sim).unwrap();
+    assert!(aim.trials <= MAX_TRIALS, "{} trials", aim.trials);
+    let per_trial = (TRIAL_HORIZON / DT).ceil() as u64 + 1;
+    assert!(aim.ticks <= aim.trials as u64 * per_trial, "{} ticks for {} trials", aim.ticks, aim.trials);
+}
+
+#[test]
+fn waits_for_its_turn() {
+    let config = serde_json::from_value(json!({ "rules": { "mode": "TurnBased", "countdown": 0.1 } })).unwrap();
+    let mut sim = common::simulation_with(config, scene());
+    sim.start_match().unwrap();
+    for _ in 0..12 {
+        sim.step(DT);
+    }
+    // player 0 opens the round
+    let mut ai = computer("Hard", vec![banana(false, 5.0)]);
+    for _ in 0..120 {
+        assert!(ai.think(&sim, DT).is_none());
+    }
+}
+
+#[test]
+fn needs_a_weapon() {
+    match Ai::new(1, &AiConfig::default()) {
+        Err(GameError::Config(_)) => {}
+        other => panic!("expected a config error, got {:?}", other.map(|ai| ai.gorilla())),
+    }
+}
@@ -0,0 +1,75 @@
+//! Bricks wear down under impacts and blasts before they break.
+
+mod common;
+
+use common::{banana, scene, shot, simulation, DT};
+use minimal::Simulation;
+use nalgebra::Vector2;
+use serde_json::Value;
+
+fn settled(scene: Value) -> Simulation {
+    let mut sim = simulation(scene);
+    for _ in 0..60 {
+        sim.step(DT);
+    }
+    sim
+}
+
+fn with_strength(strength: f64) -> Value {
+    let mut scene = scene();
+    for building in scene["buildings"].as_array_mut().unwrap() {
+        building["strength"] = strength.into();
+    }
+    scene
+}
+
+/// Throws a plain banana into the facade of the right building.
+fn throw_at_facade(sim: &mut Simulation) {
+    sim.shoot(&shot(0, -3.0, 3.5, 0.0, 14.0, banana(false, 10.0)));
+    for _ in 0..60 {
+        sim.step(DT);
+    }
+}
+
+#[test]
+fn settling_does_not_hurt_buildings() {
+    let sim = settled(scene());
+    assert!(sim.bricks().iter().all(|brick| brick.hit_points == brick.strength && brick.strength == 0.1));
+}
+
+#[test]
+fn banana_cracks_bricks_it_hits() {
+    let mut sim = settled(scene());
+    let before = sim.bricks().len();
+    throw_at_facade(&mut sim);
+
+    assert_eq!(sim.bricks().len(), before);
+    let worn: Vec<f64> = sim.bricks().iter().map(|brick| brick.wear()).filter(|&wear| wear > 0.0).collect();
+    assert!(!worn.is_empty());
+    assert!(worn.iter().all(|&wear| wear < 1.0), "{:?}", worn);
+}
+
+#[test]
+fn weak_material_breaks_where_strong_holds() {
+    let mut weak = settled(with_strength(0.01));
+    let mut strong = settled(with_strength(10.0));
+    let (weak_before, strong_before) = (weak.bricks().len(), strong.bricks().len());
+    throw_at_facade(&mut weak);
+    throw_at_facade(&mut strong);
+
+    assert!(weak.bricks().len() < weak_before);
+    assert_eq!(strong.bricks().len(), strong_before);
+    assert!(strong.bricks().iter().all(|brick| brick.wear() < 0.05));
+}
+
+#[test]
+fn blast_rim_only_damages() {
+    let mut sim = settled(scene());
+    let before = sim.bricks().len();
+    // right next to the right building, only its outer bricks are in reach
+    sim.explode(Vector2::new(5.1, 3.9), 0.6, 0.0, 0.2, None);
+    sim.step(DT);
+
+    assert_eq!(sim.bricks().len(), before);
+    assert!(sim.bricks().iter().any(|brick| brick.wear() > 0.0 && brick.wear() < 1.0));
+}
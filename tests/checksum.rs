@@ -0,0 +1,106 @@
+//! Checksums tell when two simulations that should agree went different ways, and where.
+
+mod common;
+
+use common::{banana, scene, shot, simulation, simulation_with, DT};
+use minimal::checksum::Divergence;
+use minimal::entities::Kind;
+use minimal::replay::Action;
+use minimal::{Point, Simulation};
+use serde_json::json;
+
+fn recorded(entity_checksum_interval: u64) -> Simulation {
+    let config = serde_json::from_value(json!({ "seed": 5, "record": true, "entity_checksum_interval": entity_checksum_interval })).unwrap();
+    let mut sim = simulation_with(config, scene());
+    for _ in 0..30 {
+        sim.step(DT);
+    }
+    sim.shoot(&shot(0, -3.0, 2.8, -0.1, 14.0, banana(false, 5.0)));
+    for _ in 0..90 {
+        sim.step(DT);
+    }
+    sim
+}
+
+#[test]
+fn equal_simulations_have_equal_checksums() {
+    let mut a = simulation(scene());
+    let mut b = simulation(scene());
+    for _ in 0..30 {
+        a.step(DT);
+        b.step(DT);
+        assert_eq!(a.checksum(), b.checksum());
+    }
+
+    b.move_gorilla(1, serde_json::from_value::<Point>(json!({ "x": 0.0, "y": -0.001 })).unwrap()).unwrap();
+    assert_ne!(a.checksum(), b.checksum());
+    let moved: Vec<_> = a.entity_checksums().into_iter().zip(b.entity_checksums())
+        .filter(|(a, b)| a != b)
+        .map(|(a, _)| a.0)
+        .collect();
+    assert_eq!(moved.len(), 1);
+    assert_eq!(moved[0].kind, Kind::Gorilla);
+}
+
+#[test]
+fn match_and_seed_are_part_of_the_checksum() {
+    let a = simulation(scene());
+    let mut paused = simulation(scene());
+    paused.toggle_pause();
+    let config = serde_json::from_value(json!({ "seed": 6 })).unwrap();
+    let reseeded = simulation_with(config, scene());
+
+    for other in &[paused, reseeded] {
+        assert_ne!(a.checksum(), other.checksum());
+        assert_eq!(a.entity_checksums(), other.entity_checksums());
+    }
+}
+
+#[test]
+fn every_tick_is_recorded() {
+    let sim = recorded(60);
+    let replay = sim.replay();
+    assert_eq!(replay.checksums.len() as u64, sim.ticks());
+    assert_eq!(*replay.checksums.last().unwrap(), sim.checksum());
+    let ticks: Vec<u64> = replay.entity_checksums.iter().map(|checksums| checksums.tick).collect();
+    assert_eq!(ticks, vec![60, 120]);
+}
+
+#[test]
+fn untouched_replay_verifies() {
+    let sim = recorded(60);
+    assert_eq!(sim.replay().verify().unwrap(), None);
+}
+
+#[test]
+fn tampered_shot_is_caught_at_the_banana() {
+    let sim = recorded(1);
+    let mut replay = sim.replay().clone();
+    for action in &mut replay.actions {
+        if let Action::Shoot { spin, .. } = action {
+            *spin = spin.map(|spin| spin + 1.0);
+        }
+    }
+
+    let divergence = replay.verify().unwrap().expect("a different spin should show");
+    // the banana spins differently from its first tick on
+    assert_eq!(divergence.tick, 31);
+    assert_eq!(divergence.expected, sim.replay().checksums[30]);
+    assert_eq!(divergence.entity.map(|id| id.kind), Some(Kind::Banana));
+}
+
+#[test]
+fn tampered_checksum_is_reported_as_is() {
+    let sim = recorded(60);
+    let mut replay = sim.replay().clone();
+    replay.checksums[99] ^= 1;
+
+    let divergence = replay.verify().unwrap().unwrap();
+    assert_eq!(divergence, Divergence {
+        tick: 100,
+        expected: sim.replay().checksums[99] ^ 1,
+        actual: sim.replay().checksums[99],
+        // every entity still agrees at tick 120
+        entity: None,
+    });
+}
@@ -0,0 +1,60 @@
+//! Scene and shot builders shared by the integration tests.
+#![allow(dead_code)]
+
+use minimal::{GameConfig, SceneConfig, Shot, Simulation};
+use serde_json::{json, Value};
+
+pub const DT: f64 = 1.0 / 60.0;
+
+pub fn game_config() -> GameConfig {
+    serde_json::from_value(json!({ "width": 800, "height": 500 })).unwrap()
+}
+
+/// A config that records a replay.
+pub fn recording(seed: u64) -> GameConfig {
+    serde_json::from_value(json!({ "width": 800, "height": 500, "seed": seed, "record": true })).unwrap()
+}
+
+/// Two small buildings facing each other, players on the roofs.
+pub fn scene() -> Value {
+    let margin = 0.00000000001;
+    json!({
+        "margin": margin,
+        "gravity": 9.81,
+        "box_radx": 0.21 - margin,
+        "box_rady": 0.10 - margin,
+        "ground_radx": 125.0 - margin,
+        "ground_rady": 4.5,
+        "ground_x": 0,
+        "ground_y": 9,
+        "buildings": [
+            { "x": -4.0, "w": 3, "h": 5, "fill_style": "#04aaac" },
+            { "x":  4.0, "w": 3, "h": 5, "fill_style": "#ac0204" },
+        ],
+        "player_a": { "x": -4.0, "y": 2.5, "radx": 0.2, "rady": 0.3, "inertia": 0.1 },
+        "player_b": { "x":  4.0, "y": 2.5, "radx": 0.2, "rady": 0.3, "inertia": 0.1 },
+    })
+}
+
+pub fn banana(explosive: bool, ttl: f64) -> Value {
+    json!({ "w": 0.3, "h": 0.1, "inertia": 1, "ttl": ttl, "cost": 0.3, "explosive": explosive })
+}
+
+pub fn shot(gorilla_id: usize, x: f64, y: f64, rot: f64, power: f64, config: Value) -> Shot {
+    serde_json::from_value(json!({
+        "x": x, "y": y, "rot": rot, "power": power,
+        "gorilla_id": gorilla_id,
+        "config": config,
+    })).unwrap()
+}
+
+pub fn simulation(scene: Value) -> Simulation {
+    simulation_with(game_config(), scene)
+}
+
+pub fn simulation_with(config: GameConfig, scene: Value) -> Simulation {
+    let scene: SceneConfig = serde_json::from_value(scene).unwrap();
+    let mut sim = Simulation::new(config);
+    sim.set_scene(&scene).unwrap();
+    sim
+}
@@ -0,0 +1,82 @@
+//! Malformed configs are reported with the offending field instead of panicking.
+
+mod common;
+
+use common::{scene, simulation};
+use minimal::error::{parse_config, ConfigError};
+use minimal::{GameError, SceneConfig};
+use serde_json::json;
+
+fn scene_error(scene: serde_json::Value) -> ConfigError {
+    match parse_config::<SceneConfig>("set_scene", scene) {
+        Err(GameError::Config(err)) => err,
+        other => panic!("expected a config error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn wrong_type_names_field_and_expectation() {
+    let mut level = scene();
+    level["name"] = json!("canyon");
+    level["buildings"][1]["w"] = json!("wide");
+
+    let err = scene_error(level);
+    assert_eq!(err.context, "set_scene");
+    assert_eq!(err.level.as_deref(), Some("canyon"));
+    assert_eq!(err.field.as_deref(), Some("buildings[1].w"));
+    assert_eq!(err.expected.as_deref(), Some("usize"));
+}
+
+#[test]
+fn missing_field_is_reported_with_its_path() {
+    let mut level = scene();
+    level["player_b"].as_object_mut().unwrap().remove("radx");
+
+    let err = scene_error(level);
+    assert_eq!(err.field.as_deref(), Some("player_b.radx"));
+    assert!(err.message.contains("missing field"), "{}", err.message);
+    assert!(!err.message.contains(" line "), "positions in our internal copy are meaningless: {}", err.message);
+}
+
+#[test]
+fn top_level_field() {
+    let mut level = scene();
+    level["gravity"] = json!([0, 9.81]);
+
+    let err = scene_error(level);
+    assert_eq!(err.field.as_deref(), Some("gravity"));
+    assert_eq!(err.level, None);
+}
+
+#[test]
+fn odd_keys_and_strings_dont_shift_the_path() {
+    let mut level = scene();
+    level["name"] = json!("\"quoted\": [ {");
+    level["buildings"][0]["fill_style"] = json!("{\n[");
+    level["buildings"][0]["\": [\"extra"] = json!({ "\"": [1, 2, { "}": "]" }] });
+    level["buildings"][1]["w"] = json!("wide");
+
+    let err = scene_error(level);
+    assert_eq!(err.field.as_deref(), Some("buildings[1].w"));
+    assert_eq!(err.level.as_deref(), Some("\"quoted\": [ {"));
+}
+
+#[test]
+fn unknown_gorilla_is_an_error() {
+    let mut sim = simulation(scene());
+    assert!(sim.gorilla_pos(1).is_ok());
+    match sim.gorilla_pos(2) {
+        Err(GameError::NoSuchGorilla { index: 2, count: 2 }) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(sim.move_gorilla(5, minimal::Point { x: 0.0, y: 0.0 }).is_err());
+}
+
+#[test]
+fn serialize_failures_are_not_config_errors() {
+    let err = GameError::serialize("snapshot", "key must be a string");
+    let value = serde_json::to_value(&err).unwrap();
+    assert_eq!(value["kind"], "Serialize");
+    assert_eq!(value["context"], "snapshot");
+    assert_eq!(err.to_string(), "snapshot: could not serialize: key must be a string");
+}
@@ -0,0 +1,87 @@
+//! Broken bricks can leave fragments behind, within a budget.
+
+mod common;
+
+use common::{scene, simulation, DT};
+use minimal::Simulation;
+use nalgebra::Vector2;
+use serde_json::{json, Value};
+
+fn settled(scene: Value) -> Simulation {
+    let mut sim = simulation(scene);
+    for _ in 0..60 {
+        sim.step(DT);
+    }
+    sim
+}
+
+fn with_debris(debris: Value) -> Value {
+    let mut scene = scene();
+    scene["debris"] = debris;
+    scene
+}
+
+/// Blows a hole into the left side of the right building, returns how many bricks broke.
+fn blast(sim: &mut Simulation) -> usize {
+    let before = sim.bricks().len();
+    sim.explode(Vector2::new(3.2, 4.0), 0.6, 0.05, 0.5, None);
+    sim.step(DT);
+    before - sim.bricks().len()
+}
+
+#[test]
+fn bricks_vanish_without_debris_config() {
+    let mut sim = settled(scene());
+    assert!(blast(&mut sim) > 0);
+    assert!(sim.debris().is_empty());
+}
+
+#[test]
+fn broken_bricks_split_into_fragments() {
+    let mut sim = settled(with_debris(json!({ "split": 2 })));
+    let broken = blast(&mut sim);
+
+    assert!(broken > 0);
+    assert_eq!(sim.debris().len(), broken * 4);
+    assert!(sim.debris().iter().all(|fragment| fragment.fill_style == "#ac0204"));
+    // the blast pushed the bricks to the right, their fragments keep going that way
+    let drift: f64 = sim.debris().iter()
+        .map(|fragment| sim.world().rigid_body(fragment.body).unwrap().velocity().linear.x)
+        .sum();
+    assert!(drift > 0.0, "{}", drift);
+}
+
+#[test]
+fn fragments_expire() {
+    let mut sim = settled(with_debris(json!({ "ttl": 0.5 })));
+    blast(&mut sim);
+    let first = sim.entities().debris.ids().to_vec();
+    assert!(!first.is_empty());
+
+    for _ in 0..40 {
+        sim.step(DT);
+    }
+    // falling bricks may have broken since, but the first fragments are gone
+    assert!(sim.entities().debris.ids().iter().all(|id| !first.contains(id)));
+}
+
+#[test]
+fn budget_drops_the_oldest_fragments() {
+    let mut sim = settled(with_debris(json!({ "split": 3, "budget": 10 })));
+    let broken = blast(&mut sim);
+
+    assert!(broken * 9 > 10);
+    assert_eq!(sim.debris().len(), 10);
+}
+
+#[test]
+fn fragments_over_budget_are_never_spawned() {
+    let mut sim = settled(with_debris(json!({ "split": 8, "budget": 5 })));
+    let broken = blast(&mut sim);
+
+    assert!(broken > 0);
+    assert_eq!(sim.debris().len(), 5);
+    // fragments spawned and dropped again within the tick would have taken slots of their own
+    let ids = sim.entities().debris.ids();
+    assert!(ids.iter().all(|id| id.index < 5 && id.generation == 0), "{:?}", ids);
+}
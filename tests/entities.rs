@@ -0,0 +1,86 @@
+//! Entity ids stay unique even when the physics engine reuses collider handles.
+
+mod common;
+
+use common::{banana as banana_config, scene, shot, simulation, DT};
+use minimal::entities::{Kind, Store};
+use minimal::GameEvent;
+
+#[test]
+fn removed_ids_never_resolve_again() {
+    let mut store = Store::new(Kind::Brick);
+    let a = store.insert("a");
+    let b = store.insert("b");
+    let c = store.insert("c");
+
+    let removed = store.remove_where(|&item| item == "b");
+    assert_eq!(removed, vec![(b, "b")]);
+    assert_eq!(store.items(), &["a", "c"]);
+    assert_eq!((store.get(a), store.get(b), store.get(c)), (Some(&"a"), None, Some(&"c")));
+
+    // takes the free slot, but with a new generation
+    let d = store.insert("d");
+    assert_eq!(d.index, b.index);
+    assert_ne!(d, b);
+    assert_eq!((store.get(b), store.get(d)), (None, Some(&"d")));
+    assert_eq!(store.position(d), Some(2));
+}
+
+#[test]
+fn ids_of_other_kinds_do_not_resolve() {
+    let mut bricks = Store::new(Kind::Brick);
+    let mut bananas = Store::new(Kind::Banana);
+    let brick = bricks.insert(1);
+    bananas.insert(2);
+    assert_eq!(bananas.get(brick), None);
+}
+
+#[test]
+fn remove_oldest_keeps_the_newest() {
+    let mut store = Store::new(Kind::Debris);
+    let ids: Vec<_> = (0..5).map(|i| store.insert(i)).collect();
+    let removed: Vec<_> = store.remove_oldest(2).into_iter().map(|(_, item)| item).collect();
+    assert_eq!(removed, vec![0, 1]);
+    assert_eq!(store.ids(), &ids[2..]);
+}
+
+#[test]
+fn event_ids_point_at_live_entities() {
+    let mut sim = simulation(scene());
+    for _ in 0..60 {
+        sim.step(DT);
+    }
+    sim.shoot(&shot(0, -3.0, 2.8, 0.0, 14.0, banana_config(false, 10.0)));
+    let banana = match sim.poll_events()[0] {
+        GameEvent::ShotFired { banana, .. } => banana,
+        ref other => panic!("{:?}", other),
+    };
+    assert_eq!(banana.kind, Kind::Banana);
+    assert!(sim.entities().bananas.get(banana).is_some());
+
+    let mut brick = None;
+    for _ in 0..30 {
+        sim.step(DT);
+        for event in sim.poll_events() {
+            if let GameEvent::BananaHitBrick { brick: id, .. } = event {
+                brick = Some(id);
+            }
+        }
+    }
+    let brick = brick.expect("the banana hit the facade");
+    assert!(sim.entities().bricks.get(brick).is_some());
+
+    // once the banana is gone the next one takes over its slot, but not its id
+    for _ in 0..60 {
+        sim.step(DT);
+    }
+    assert!(sim.bananas().is_empty());
+    sim.shoot(&shot(1, 3.0, 2.0, 3.0, 2.0, banana_config(false, 10.0)));
+    let second = match sim.poll_events()[0] {
+        GameEvent::ShotFired { banana, .. } => banana,
+        ref other => panic!("{:?}", other),
+    };
+    assert_eq!(second.index, banana.index);
+    assert_ne!(second, banana);
+    assert!(sim.entities().bananas.get(banana).is_none());
+}
@@ -0,0 +1,85 @@
+//! The event queue tells the frontend about throws, hits and breakage.
+
+mod common;
+
+use common::{banana, scene, shot, simulation, DT};
+use minimal::{GameEvent, Simulation};
+use serde_json::json;
+
+fn settled() -> Simulation {
+    let mut sim = simulation(scene());
+    for _ in 0..60 {
+        sim.step(DT);
+    }
+    sim.poll_events();
+    sim
+}
+
+#[test]
+fn throws_and_cooldowns_are_reported() {
+    let mut sim = settled();
+    sim.shoot(&shot(0, -3.0, 3.5, 0.0, 14.0, banana(false, 10.0)));
+    sim.shoot(&shot(0, -3.0, 3.5, 0.0, 14.0, banana(false, 10.0)));
+
+    let events = sim.poll_events();
+    assert_eq!(events.len(), 2);
+    match events[0] {
+        GameEvent::ShotFired { gorilla: 0, x, power, .. } => assert_eq!((x, power), (-3.0, 14.0)),
+        ref other => panic!("{:?}", other),
+    }
+    match events[1] {
+        GameEvent::ShotRejectedCooldown { gorilla: 0, remaining } => assert!(remaining > 0.0 && remaining <= 0.3),
+        ref other => panic!("{:?}", other),
+    }
+    assert!(sim.poll_events().is_empty(), "polling drains the queue");
+}
+
+#[test]
+fn no_throws_during_the_countdown() {
+    let mut sim = settled();
+    sim.start_match().unwrap();
+    sim.shoot(&shot(1, 3.0, 3.5, 0.0, 14.0, banana(false, 10.0)));
+    match sim.poll_events()[..] {
+        [GameEvent::ShotRejectedNotStarted { gorilla: 1, remaining }] => assert!(remaining > 0.0),
+        ref other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn hits_and_breakage_carry_ids_and_positions() {
+    let mut sim = settled();
+    let mut config = banana(true, 10.0);
+    config["blast_damage"] = json!(1.0);
+    sim.shoot(&shot(0, -3.0, 2.8, 0.0, 14.0, config));
+    for _ in 0..30 {
+        sim.step(DT);
+    }
+    let events = sim.poll_events();
+
+    let banana = match events[0] {
+        GameEvent::ShotFired { banana, .. } => banana,
+        ref other => panic!("{:?}", other),
+    };
+    let hit = events.iter().find_map(|event| match *event {
+        GameEvent::BananaHitBrick { banana: b, x, impulse, by, .. } if b == banana => Some((x, impulse, by)),
+        _ => None,
+    });
+    let (x, impulse, by) = hit.expect("the banana hit the facade");
+    assert!((x - 3.2).abs() < 0.4 && impulse > 0.0 && by == Some(0), "{:?}", hit);
+
+    let destroyed: Vec<_> = events.iter().filter_map(|event| match *event {
+        GameEvent::BrickDestroyed { x, by, .. } => Some((x, by)),
+        _ => None,
+    }).collect();
+    assert!(!destroyed.is_empty());
+    assert!(destroyed.iter().all(|&(x, by)| x > 2.5 && by == Some(0)), "{:?}", destroyed);
+}
+
+#[test]
+fn events_serialize_with_their_name() {
+    let event = GameEvent::ShotRejectedCooldown { gorilla: 1, remaining: 0.25 };
+    assert_eq!(
+        serde_json::to_value(&event).unwrap(),
+        json!({ "event": "ShotRejectedCooldown", "gorilla": 1, "remaining": 0.25 }),
+    );
+}
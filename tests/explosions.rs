@@ -0,0 +1,80 @@
+//! Explosive bananas blow holes into buildings and push things around.
+
+mod common;
+
+use common::{banana, scene, shot, simulation, DT};
+use minimal::{GameEvent, Simulation};
+use nalgebra::Vector2;
+use serde_json::{json, Value};
+
+fn settled() -> Simulation {
+    let mut sim = simulation(scene());
+    for _ in 0..60 {
+        sim.step(DT);
+    }
+    sim
+}
+
+fn blast(radius: f64) -> Value {
+    let mut config = banana(true, 10.0);
+    config["blast_radius"] = json!(radius);
+    config
+}
+
+/// Throws `config` into the facade of the right building and returns how many bricks it cost.
+fn bricks_lost(config: Value) -> (usize, Vec<GameEvent>) {
+    let mut sim = settled();
+    let before = sim.bricks().len();
+    sim.poll_events();
+    sim.shoot(&shot(0, -3.0, 3.5, 0.0, 14.0, config));
+    for _ in 0..60 {
+        sim.step(DT);
+    }
+    (before - sim.bricks().len(), sim.poll_events())
+}
+
+#[test]
+fn explosion_breaks_bricks_near_its_center() {
+    let (plain, _) = bricks_lost(banana(false, 10.0));
+    let (small, events) = bricks_lost(blast(0.6));
+    let (large, _) = bricks_lost(blast(1.2));
+
+    assert_eq!(plain, 0);
+    assert!(small >= 1);
+    assert!(large > small, "{} <= {}", large, small);
+
+    let explosions: Vec<_> = events.iter().filter(|e| matches!(e, GameEvent::Explosion { .. })).collect();
+    assert_eq!(explosions.len(), 1, "a banana only goes off once");
+    match explosions[0] {
+        GameEvent::Explosion { x, radius, by, .. } => {
+            assert_eq!((*radius, *by), (0.6, Some(0)));
+            // the facade of the right building is near x = 3.2
+            assert!((x - 3.2).abs() < 0.4, "went off at {}", x);
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn blast_pushes_bodies_away_with_falloff() {
+    let mut near = settled();
+    let mut far = settled();
+    let gorilla = near.gorilla_pos(1).unwrap();
+
+    near.explode(Vector2::new(gorilla.x + 0.2, gorilla.y), 1.0, 0.05, 0.5, None);
+    far.explode(Vector2::new(gorilla.x + 0.8, gorilla.y), 1.0, 0.05, 0.5, None);
+
+    let speed = |sim: &Simulation| sim.world().rigid_body(sim.gorillas()[1].body).unwrap().velocity().linear.x;
+    assert!(speed(&near) < 0.0, "pushed to the left");
+    assert!(speed(&near) < speed(&far) && speed(&far) < 0.0, "{} vs {}", speed(&near), speed(&far));
+}
+
+#[test]
+fn bricks_out_of_reach_survive() {
+    let mut sim = settled();
+    let before = sim.bricks().len();
+    // well above the left building
+    sim.explode(Vector2::new(-4.0, -2.0), 1.0, 0.05, 0.5, None);
+    sim.step(DT);
+    assert_eq!(sim.bricks().len(), before);
+}
@@ -0,0 +1,93 @@
+//! Bananas hurt gorillas, enough of them knock one out.
+
+mod common;
+
+use common::{banana, scene, shot, simulation, DT};
+use minimal::{GameEvent, Simulation};
+use serde_json::Value;
+use std::f64::consts::FRAC_PI_2;
+
+fn settled() -> Simulation {
+    let mut sim = simulation(scene());
+    for _ in 0..60 {
+        sim.step(DT);
+    }
+    sim
+}
+
+/// Drops a banana from gorilla 0 onto the head of gorilla 1.
+fn drop_on_right_gorilla(sim: &mut Simulation, config: Value) -> Vec<GameEvent> {
+    sim.poll_events();
+    sim.shoot(&shot(0, 4.0, 1.5, FRAC_PI_2, 5.0, config));
+    for _ in 0..60 {
+        sim.step(DT);
+    }
+    sim.poll_events()
+}
+
+fn with(mut config: Value, key: &str, value: f64) -> Value {
+    config[key] = value.into();
+    config
+}
+
+#[test]
+fn banana_hurts_the_gorilla_it_hits() {
+    let mut sim = settled();
+    let events = drop_on_right_gorilla(&mut sim, banana(false, 10.0));
+
+    let health = sim.gorillas()[1].health;
+    assert!(health < 100.0 && health > 0.0, "health is {}", health);
+    assert_eq!(sim.gorillas()[0].health, 100.0);
+    // it may bounce and hit again
+    let mut damage = 0.0;
+    for event in &events {
+        match event {
+            GameEvent::BananaHitGorilla { gorilla: 1, damage: d, by: Some(0), .. } => damage += d,
+            GameEvent::BananaHitGorilla { .. } | GameEvent::GorillaDied { .. } => panic!("expected hits on gorilla 1, got {:?}", event),
+            _ => {}
+        }
+    }
+    assert!((100.0 - damage - health).abs() < 1e-9);
+}
+
+#[test]
+fn damage_scales_with_config_and_explosives() {
+    let mut plain = settled();
+    drop_on_right_gorilla(&mut plain, banana(false, 10.0));
+    let mut explosive = settled();
+    drop_on_right_gorilla(&mut explosive, banana(true, 10.0));
+    let mut harmless = settled();
+    drop_on_right_gorilla(&mut harmless, with(banana(false, 10.0), "damage", 0.0));
+
+    let lost = |sim: &Simulation| 100.0 - sim.gorillas()[1].health;
+    assert!(lost(&explosive) > lost(&plain), "{} <= {}", lost(&explosive), lost(&plain));
+    assert_eq!(lost(&harmless), 0.0);
+}
+
+#[test]
+fn gorilla_dies_once_and_stops_throwing() {
+    let mut sim = settled();
+    let events = drop_on_right_gorilla(&mut sim, with(banana(false, 10.0), "damage", 10_000.0));
+
+    let deaths: Vec<_> = events.iter().filter(|e| matches!(e, GameEvent::GorillaDied { .. })).collect();
+    assert_eq!(deaths, vec![&GameEvent::GorillaDied { gorilla: 1, by: Some(0) }]);
+    assert_eq!(sim.gorillas()[1].health, 0.0);
+    assert!(!sim.gorillas()[1].is_alive());
+
+    let bananas = sim.bananas().len();
+    sim.shoot(&shot(1, 4.0, 1.0, 0.0, 5.0, banana(false, 10.0)));
+    assert_eq!(sim.bananas().len(), bananas, "dead gorillas can't throw");
+}
+
+#[test]
+fn fresh_banana_spares_its_thrower() {
+    let mut sim = settled();
+    let pos = sim.gorilla_pos(0).unwrap();
+    // spawned right inside the thrower, as the gamepad controls do
+    sim.shoot(&shot(0, pos.x, pos.y, 0.0, 1.0, banana(false, 10.0)));
+    for _ in 0..10 {
+        sim.step(DT);
+    }
+    assert_eq!(sim.gorillas()[0].health, 100.0);
+    assert!(!sim.poll_events().iter().any(|e| matches!(e, GameEvent::BananaHitGorilla { .. })));
+}
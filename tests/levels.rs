@@ -0,0 +1,159 @@
+//! Level files and the built-in level registry.
+
+mod common;
+
+use common::game_config;
+use minimal::levels::LevelFormat;
+use minimal::{GameError, Level, LevelRegistry, Simulation};
+use serde_json::{json, Value};
+
+fn buildings(level: &Level) -> Vec<Value> {
+    serde_json::to_value(&level.scene).unwrap()["buildings"].as_array().unwrap().clone()
+}
+
+fn config_error(err: GameError) -> minimal::error::ConfigError {
+    match err {
+        GameError::Config(err) => err,
+        other => panic!("expected a config error, got {:?}", other),
+    }
+}
+
+#[test]
+fn builtin_levels_are_valid_and_build() {
+    let registry = LevelRegistry::builtin().unwrap();
+    assert_eq!(registry.names(), vec!["stairs", "valley", "pyramid", "mirror"]);
+
+    for name in registry.names() {
+        let scene = registry.get(name).unwrap();
+        scene.validate().unwrap_or_else(|problems| panic!("{}: {:?}", name, problems));
+
+        let mut sim = Simulation::new(game_config());
+        sim.set_scene(scene).unwrap();
+        assert_eq!(sim.gorillas().len(), 2);
+    }
+}
+
+#[test]
+fn mirror_appends_the_other_half() {
+    let registry = LevelRegistry::builtin().unwrap();
+    let scene = serde_json::to_value(registry.get("mirror").unwrap()).unwrap();
+    let xs: Vec<f64> = scene["buildings"].as_array().unwrap().iter().map(|b| b["x"].as_f64().unwrap()).collect();
+    assert_eq!(xs, vec![-1.6, -9.6, -7.3, -4.6, 1.6, 9.6, 7.3, 4.6]);
+    assert_eq!(scene["name"], "mirror");
+}
+
+#[test]
+fn mirror_keeps_buildings_on_the_axis_once() {
+    let level = Level::from_value(json!({
+        "version": 1,
+        "name": "tower",
+        "mirror": true,
+        "scene": {
+            "buildings": [
+                { "x": 0.0, "w": 3, "h": 10, "fill_style": "white" },
+                { "x": -5.0, "w": 3, "h": 10, "fill_style": "white" },
+            ],
+            "player_a": { "x": -5.0, "y": -4.0, "radx": 0.2, "rady": 0.3, "inertia": 0.1 },
+            "player_b": { "x": 5.0, "y": -4.0, "radx": 0.2, "rady": 0.3, "inertia": 0.1 },
+        },
+    })).unwrap();
+    assert_eq!(buildings(&level).len(), 3);
+}
+
+#[test]
+fn ron_and_json_describe_the_same_level() {
+    let json = r#"{
+        "version": 1,
+        "name": "twins",
+        "scene": {
+            "gravity": 9.81,
+            "buildings": [{ "x": -4.0, "w": 3, "h": 5, "fill_style": "white" }],
+            "player_a": { "x": -4.0, "y": 2.5, "radx": 0.2, "rady": 0.3, "inertia": 0.1 },
+            "player_b": { "x": 4.0, "y": 2.5, "radx": 0.2, "rady": 0.3, "inertia": 0.1 }
+        }
+    }"#;
+    let ron = r#"
+        /* same level, other syntax */
+        LevelFile(
+            version: 1,
+            name: "twins",
+            scene: (
+                gravity: Some(9.81),
+                buildings: [(x: -4.0, w: 3, h: 5, fill_style: "white")],
+                player_a: (x: -4.0, y: 2.5, radx: 0.2, rady: 0.3, inertia: 0.1), // left
+                player_b: (x: 4.0, y: 2.5, radx: 0.2, rady: 0.3, inertia: 0.1),
+            ),
+        )
+    "#;
+    let from_json = Level::parse(json, LevelFormat::Json).unwrap();
+    let from_ron = Level::parse(ron, LevelFormat::Ron).unwrap();
+    assert_eq!(serde_json::to_value(&from_json.scene).unwrap(), serde_json::to_value(&from_ron.scene).unwrap());
+}
+
+#[test]
+fn ron_strings_comments_options_and_enums() {
+    let level = Level::parse(r##"
+        // a line comment
+        (
+            version: 1,
+            name: "say \"hi\"\t/* not a comment */",
+            scene: (
+                gravity: None, /* a block /* nested */ comment */
+                wind: Some((mode: Some(Random), x: Some(2.0))),
+                buildings: [(x: 0.0, w: 3, h: 5, fill_style: r#"#fff"#,),],
+                player_a: (x: -4.0, y: 2.5, radx: 0.2, rady: 0.3, inertia: 0.1,),
+                player_b: (x: 4.0, y: 2.5, radx: 0.2, rady: 0.3, inertia: 0.1),
+            ),
+        )
+    "##, LevelFormat::Ron).unwrap();
+    assert_eq!(level.name, "say \"hi\"\t/* not a comment */");
+    let scene = serde_json::to_value(&level.scene).unwrap();
+    assert_eq!(scene["gravity"], Value::Null);
+    assert_eq!(scene["wind"]["mode"], "Random");
+    assert_eq!(scene["wind"]["x"], 2.0);
+    assert_eq!(scene["buildings"][0]["fill_style"], "#fff");
+}
+
+#[test]
+fn ron_field_errors_have_a_path_and_a_position() {
+    let err = config_error(Level::parse(
+        "(version: 1,\n  name: \"tall\",\n  scene: (buildings: [(x: 0.0, w: 3, h: \"high\", fill_style: \"white\")]))",
+        LevelFormat::Ron,
+    ).unwrap_err());
+    assert_eq!(err.field.as_deref(), Some("scene.buildings[0].h"));
+    assert_eq!(err.level.as_deref(), Some("tall"));
+    assert!(err.message.contains("line 3"), "{}", err.message);
+
+    let newer = config_error(Level::parse("(version: 2, name: \"future\", scene: Moon(1))", LevelFormat::Ron).unwrap_err());
+    assert_eq!(newer.field.as_deref(), Some("version"));
+}
+
+#[test]
+fn bad_level_files_report_where() {
+    let wrong_type = Level::parse(
+        r#"(version: 1, name: "broken", scene: (buildings: [(x: 0.0, w: -3, h: 5, fill_style: "white")]))"#,
+        LevelFormat::Ron,
+    ).unwrap_err();
+    let err = config_error(wrong_type);
+    assert_eq!(err.field.as_deref(), Some("scene.buildings[0].w"));
+    assert_eq!(err.level.as_deref(), Some("broken"));
+
+    let syntax = config_error(Level::parse("(version: 1,\n  name: \"x\" scene: ())", LevelFormat::Ron).unwrap_err());
+    assert!(syntax.message.contains("line 2"), "{}", syntax.message);
+
+    let newer = config_error(Level::from_value(json!({ "version": 2, "name": "future" })).unwrap_err());
+    assert_eq!(newer.field.as_deref(), Some("version"));
+    assert_eq!(newer.expected.as_deref(), Some("1"));
+}
+
+#[test]
+fn unknown_level_lists_the_known_ones() {
+    let registry = LevelRegistry::builtin().unwrap();
+    match registry.get("moon") {
+        Err(GameError::NoSuchLevel { name, known }) => {
+            assert_eq!(name, "moon");
+            assert_eq!(known.len(), 4);
+        }
+        other => panic!("expected NoSuchLevel, got {:?}", other.map(|_| ())),
+    }
+}
@@ -0,0 +1,95 @@
+//! Despawned entities leave nothing behind in the physics world.
+
+mod common;
+
+use common::{banana, scene, shot, simulation, DT};
+use minimal::simulation::DebugCounts;
+use minimal::Simulation;
+use nalgebra::Vector2;
+use serde_json::json;
+use std::f64::consts::PI;
+
+fn settled(scene: serde_json::Value) -> Simulation {
+    let mut sim = simulation(scene);
+    for _ in 0..60 {
+        sim.step(DT);
+    }
+    sim
+}
+
+/// Every entity owns exactly one body and one collider, the ground adds a collider of its own.
+fn assert_consistent(counts: &DebugCounts) {
+    let entities = counts.gorillas + counts.bricks + counts.bananas + counts.debris;
+    assert_eq!(counts.bodies, entities, "{:?}", counts);
+    assert_eq!(counts.colliders, entities + 1, "{:?}", counts);
+    assert_eq!(counts.tracked_colliders, counts.colliders, "{:?}", counts);
+    assert!(counts.remembered_bodies <= counts.bodies, "{:?}", counts);
+}
+
+#[test]
+fn expired_banana_takes_its_body_along() {
+    let mut sim = settled(scene());
+    let baseline = sim.debug_counts();
+
+    // straight up from the left roof, it comes down on nothing but air and roof
+    sim.shoot(&shot(0, -4.0, 1.0, -1.2, 3.0, banana(false, 0.5)));
+    let body = sim.bananas()[0].body;
+    assert_eq!(sim.debug_counts().bodies, baseline.bodies + 1);
+
+    for _ in 0..60 {
+        sim.step(DT);
+    }
+    assert!(sim.bananas().is_empty());
+    assert!(sim.world().rigid_body(body).is_none());
+    assert_eq!(sim.debug_counts(), baseline);
+}
+
+#[test]
+fn broken_bricks_take_their_bodies_along() {
+    let mut sim = settled(scene());
+    let before = sim.debug_counts();
+
+    sim.explode(Vector2::new(3.2, 4.0), 0.6, 0.05, 0.5, None);
+    sim.step(DT);
+    let after = sim.debug_counts();
+    let broken = before.bricks - after.bricks;
+    assert!(broken > 0);
+    // counted in the world, a body left behind without its collider counts as well
+    assert_eq!(after.bodies, before.bodies - broken, "{:?}", after);
+}
+
+#[test]
+fn breakage_and_debris_stay_consistent() {
+    let mut level = scene();
+    level["debris"] = json!({ "split": 2, "ttl": 0.5, "budget": 12 });
+    let mut sim = settled(level);
+    let before = sim.debug_counts();
+
+    // both gorillas blow a hole into the other building
+    for &(gorilla, x, rot) in &[(0, -3.0, 0.0), (1, 3.0, PI)] {
+        sim.shoot(&shot(gorilla, x, 2.8, rot, 14.0, banana(true, 10.0)));
+    }
+    for i in 0..240 {
+        sim.step(DT);
+        if i % 20 == 0 {
+            assert_consistent(&sim.debug_counts());
+        }
+    }
+
+    let after = sim.debug_counts();
+    assert!(after.bricks < before.bricks);
+    assert_consistent(&after);
+    assert_eq!((after.bananas, after.debris), (0, 0));
+}
+
+#[test]
+fn reset_returns_to_the_first_counts() {
+    let mut sim = simulation(scene());
+    let fresh = sim.debug_counts();
+    sim.shoot(&shot(0, -3.0, 2.8, 0.0, 14.0, banana(true, 10.0)));
+    for _ in 0..60 {
+        sim.step(DT);
+    }
+    sim.reset().unwrap();
+    assert_eq!(sim.debug_counts(), fresh);
+}
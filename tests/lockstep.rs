@@ -0,0 +1,149 @@
+//! Two lockstep peers over a loopback connection have to play the same match.
+
+mod common;
+
+use common::{banana, game_config, scene, shot, simulation, DT};
+use minimal::events::GameEvent;
+use minimal::lockstep::{LockstepConfig, LockstepSession};
+use minimal::net::{Input, Loopback, Message, Transport, PROTOCOL_VERSION};
+use minimal::{GameError, Point, Simulation};
+use serde_json::json;
+
+struct Peer {
+    sim: Simulation,
+    session: LockstepSession<Loopback>,
+}
+
+impl Peer {
+    fn step(&mut self) {
+        self.session.step(&mut self.sim, DT).unwrap();
+    }
+}
+
+fn config() -> LockstepConfig {
+    serde_json::from_value(json!({ "input_delay": 2, "checksum_interval": 10 })).unwrap()
+}
+
+/// The host has the scene, the guest starts out empty and takes it over.
+fn connected() -> (Peer, Peer) {
+    let (a, b) = Loopback::pair();
+    let mut host = Peer { sim: simulation(scene()), session: LockstepSession::new(a, 0, &config()).unwrap() };
+    let mut guest = Peer { sim: Simulation::new(game_config()), session: LockstepSession::new(b, 1, &config()).unwrap() };
+    host.step();
+    guest.step();
+    (host, guest)
+}
+
+fn run(host: &mut Peer, guest: &mut Peer, steps: usize) {
+    for _ in 0..steps {
+        host.step();
+        guest.step();
+    }
+}
+
+#[test]
+fn guest_starts_from_the_host_state() {
+    let (mut host, mut guest) = connected();
+    // the guest got the host's first inputs along with the state, the host catches up next
+    assert_eq!(guest.session.status().frame, Some(1));
+    run(&mut host, &mut guest, 1);
+    assert_eq!(host.session.status().frame, guest.session.status().frame);
+    assert_eq!(host.sim.checksum(), guest.sim.checksum());
+    assert_eq!(guest.sim.gorillas().len(), 2);
+}
+
+#[test]
+fn shots_land_on_the_same_frame_on_both_peers() {
+    let (mut host, mut guest) = connected();
+    run(&mut host, &mut guest, 5);
+    host.session.input(Input::Shoot { shot: shot(0, -3.0, 2.8, -0.1, 14.0, banana(false, 5.0)) });
+    guest.session.input(Input::Shoot { shot: shot(1, 3.0, 2.8, -3.0, 14.0, banana(false, 5.0)) });
+    let mut thrown = 0;
+    for _ in 0..60 {
+        run(&mut host, &mut guest, 1);
+        assert_eq!(host.session.status().frame, guest.session.status().frame);
+        assert_eq!(host.sim.checksum(), guest.sim.checksum());
+        thrown = usize::max(thrown, host.sim.bananas().len());
+    }
+    assert_eq!(thrown, 2);
+    assert_eq!(host.session.status().desync, None);
+    assert_eq!(guest.session.status().desync, None);
+}
+
+#[test]
+fn shots_for_the_other_gorilla_are_ignored() {
+    let (mut host, mut guest) = connected();
+    guest.session.input(Input::Shoot { shot: shot(0, -3.0, 2.8, -0.1, 14.0, banana(false, 5.0)) });
+    run(&mut host, &mut guest, 10);
+    assert_eq!(host.sim.bananas().len(), 0);
+    assert_eq!(guest.sim.bananas().len(), 0);
+}
+
+#[test]
+fn a_silent_peer_stalls_the_match() {
+    let (mut host, mut guest) = connected();
+    run(&mut host, &mut guest, 5);
+    let frame = host.session.status().frame;
+    for _ in 0..10 {
+        host.step();
+    }
+    let status = host.session.status();
+    assert!(status.waiting);
+    // no further than the inputs the guest sent ahead of time
+    assert_eq!(status.frame, frame.map(|frame| frame + 2));
+
+    // the guest's clock stood still as well, it goes on at its own pace and the host keeps pace with it
+    run(&mut host, &mut guest, 10);
+    assert!(host.session.status().frame > status.frame);
+    assert!(host.session.status().frame <= guest.session.status().frame.map(|frame| frame + 2));
+    assert_eq!(host.session.status().desync, None);
+}
+
+#[test]
+fn desync_is_caught_and_resynced() {
+    let (mut host, mut guest) = connected();
+    run(&mut host, &mut guest, 5);
+    // behind the session's back
+    guest.sim.move_gorilla(1, serde_json::from_value::<Point>(json!({ "x": 0.0, "y": -0.5 })).unwrap()).unwrap();
+    run(&mut host, &mut guest, 20);
+
+    assert!(host.session.status().desync.is_some());
+    run(&mut host, &mut guest, 5);
+    assert_eq!(host.session.status().frame, guest.session.status().frame);
+    assert_eq!(host.sim.checksum(), guest.sim.checksum());
+}
+
+#[test]
+fn events_not_yet_polled_survive_a_resync() {
+    let (mut host, mut guest) = connected();
+    run(&mut host, &mut guest, 5);
+    host.session.input(Input::Shoot { shot: shot(0, -3.0, 2.8, -0.1, 14.0, banana(false, 5.0)) });
+    run(&mut host, &mut guest, 5);
+    guest.sim.move_gorilla(1, serde_json::from_value::<Point>(json!({ "x": 0.0, "y": -0.5 })).unwrap()).unwrap();
+    run(&mut host, &mut guest, 25);
+
+    assert!(host.session.status().desync.is_some());
+    for peer in [&mut host, &mut guest] {
+        let events = peer.sim.poll_events();
+        assert!(events.iter().any(|event| matches!(event, GameEvent::ShotFired { .. })), "{:?}", events);
+    }
+}
+
+#[test]
+fn wrong_protocol_is_refused() {
+    let (mut a, b) = Loopback::pair();
+    let mut host = Peer { sim: simulation(scene()), session: LockstepSession::new(b, 0, &config()).unwrap() };
+    a.send(&Message::Join { player: 1, protocol: PROTOCOL_VERSION + 1 });
+    match host.session.step(&mut host.sim, DT) {
+        Err(GameError::Network { .. }) => {}
+        other => panic!("expected a network error, got {:?}", other),
+    }
+}
+
+#[test]
+fn both_playing_the_same_gorilla_is_refused() {
+    let (a, b) = Loopback::pair();
+    let mut host = Peer { sim: simulation(scene()), session: LockstepSession::new(a, 0, &config()).unwrap() };
+    let _other = LockstepSession::new(b, 0, &config()).unwrap();
+    assert!(matches!(host.session.step(&mut host.sim, DT), Err(GameError::Network { .. })));
+}
@@ -0,0 +1,103 @@
+//! Rounds, scores and pausing.
+
+mod common;
+
+use common::{banana, scene, shot, simulation_with, DT};
+use minimal::{MatchState, Simulation};
+use serde_json::json;
+use std::f64::consts::FRAC_PI_2;
+
+fn quick_match() -> Simulation {
+    let config = serde_json::from_value(json!({
+        "rules": { "rounds_to_win": 2, "countdown": 0.5, "round_over_delay": 0.5 },
+    })).unwrap();
+    simulation_with(config, scene())
+}
+
+fn run(sim: &mut Simulation, seconds: f64) {
+    for _ in 0..(seconds / DT).round() as usize {
+        sim.step(DT);
+    }
+}
+
+/// Gorilla 0 drops a deadly banana on gorilla 1.
+fn knock_out_right_gorilla(sim: &mut Simulation) {
+    let mut deadly = banana(false, 10.0);
+    deadly["damage"] = json!(10_000.0);
+    sim.shoot(&shot(0, 4.0, 1.5, FRAC_PI_2, 5.0, deadly));
+    run(sim, 0.4);
+}
+
+#[test]
+fn lobby_is_free_play() {
+    let mut sim = quick_match();
+    assert_eq!(sim.game_match().state(), &MatchState::Lobby);
+
+    sim.shoot(&shot(0, 0.0, -5.0, 0.0, 1.0, banana(false, 10.0)));
+    assert_eq!(sim.bananas().len(), 1);
+}
+
+#[test]
+fn countdown_blocks_throws_until_play() {
+    let mut sim = quick_match();
+    sim.start_match().unwrap();
+    assert!(matches!(sim.game_match().state(), MatchState::Countdown { .. }));
+    assert_eq!(sim.match_status().round, 1);
+
+    sim.shoot(&shot(0, 0.0, -5.0, 0.0, 1.0, banana(false, 10.0)));
+    assert!(sim.bananas().is_empty());
+
+    run(&mut sim, 0.6);
+    assert_eq!(sim.game_match().state(), &MatchState::Playing);
+    sim.shoot(&shot(0, 0.0, -5.0, 0.0, 1.0, banana(false, 10.0)));
+    assert_eq!(sim.bananas().len(), 1);
+}
+
+#[test]
+fn knockouts_score_and_decide_the_match() {
+    let mut sim = quick_match();
+    sim.start_match().unwrap();
+    run(&mut sim, 0.6);
+
+    knock_out_right_gorilla(&mut sim);
+    assert!(matches!(sim.game_match().state(), MatchState::RoundOver { winner: Some(0), .. }), "{:?}", sim.game_match().state());
+    assert_eq!(sim.match_status().scores, vec![1, 0]);
+
+    // the next round starts on a fresh scene
+    run(&mut sim, 0.6);
+    assert!(matches!(sim.game_match().state(), MatchState::Countdown { .. }));
+    assert_eq!(sim.match_status().round, 2);
+    assert_eq!(sim.gorillas()[1].health, 100.0);
+    assert!(sim.bananas().is_empty());
+
+    run(&mut sim, 0.6);
+    knock_out_right_gorilla(&mut sim);
+    // the last round stays on screen like any other
+    assert!(matches!(sim.game_match().state(), MatchState::RoundOver { winner: Some(0), .. }));
+    run(&mut sim, 0.5);
+    assert_eq!(sim.game_match().state(), &MatchState::MatchOver { winner: 0 });
+    assert_eq!(sim.match_status().scores, vec![2, 0]);
+
+    // a rematch starts from zero
+    sim.start_match().unwrap();
+    assert_eq!(sim.match_status().scores, vec![0, 0]);
+    assert_eq!(sim.match_status().round, 1);
+}
+
+#[test]
+fn pause_freezes_everything() {
+    let mut sim = quick_match();
+    sim.start_match().unwrap();
+    run(&mut sim, 0.2);
+    let state = sim.game_match().state().clone();
+    let pos = sim.gorilla_pos(0).unwrap();
+
+    sim.toggle_pause();
+    assert_eq!(sim.game_match().state(), &MatchState::Paused);
+    run(&mut sim, 2.0);
+    let after = sim.gorilla_pos(0).unwrap();
+    assert_eq!((after.x, after.y), (pos.x, pos.y));
+
+    sim.toggle_pause();
+    assert_eq!(sim.game_match().state(), &state);
+}
@@ -0,0 +1,112 @@
+//! A replay played on a fresh simulation ends bit for bit where the original did.
+
+mod common;
+
+use common::{banana, recording, scene, shot, simulation, simulation_with, DT};
+use minimal::replay::Action;
+use minimal::{Point, Replay, ReplayPlayer, Simulation};
+use nalgebra::Vector2;
+use serde_json::{json, Value};
+
+fn seeded() -> Simulation {
+    simulation_with(recording(77), scene())
+}
+
+fn state(sim: &Simulation) -> Value {
+    serde_json::to_value(sim.snapshot()).unwrap()
+}
+
+/// A bit of everything, with uneven frame times.
+fn play_a_match(sim: &mut Simulation) {
+    sim.start_match().unwrap();
+    for i in 0..240 {
+        sim.step(DT * if i % 3 == 0 { 0.6 } else { 1.3 });
+    }
+    sim.shoot(&shot(0, -3.0, 2.8, -0.1, 14.0, banana(true, 5.0)));
+    sim.move_gorilla(1, serde_json::from_value::<Point>(json!({ "x": 0.0, "y": -0.5 })).unwrap()).unwrap();
+    let rewind = sim.snapshot();
+    for _ in 0..45 {
+        sim.step(DT);
+    }
+    sim.restore(&rewind).unwrap();
+    sim.shoot(&shot(0, -3.0, 3.5, 0.0, 14.0, banana(false, 5.0)));
+    // rejected, still on cooldown
+    sim.shoot(&shot(0, -3.0, 3.5, 0.0, 14.0, banana(false, 5.0)));
+    sim.toggle_pause();
+    sim.step(DT);
+    sim.toggle_pause();
+    sim.explode(Vector2::new(4.0, 7.5), 1.0, 0.05, 0.5, None);
+    for _ in 0..120 {
+        sim.step(DT);
+    }
+}
+
+#[test]
+fn replay_ends_where_the_original_did() {
+    let mut sim = seeded();
+    play_a_match(&mut sim);
+
+    let mut played = sim.replay().play().unwrap();
+    assert_eq!(state(&played), state(&sim));
+    assert_eq!(played.poll_events(), sim.poll_events());
+    // and playing records the same replay again
+    assert_eq!(serde_json::to_value(played.replay()).unwrap(), serde_json::to_value(sim.replay()).unwrap());
+}
+
+#[test]
+fn replay_survives_json() {
+    let mut sim = seeded();
+    play_a_match(&mut sim);
+
+    let exported = serde_json::to_string(sim.replay()).unwrap();
+    let replay: Replay = serde_json::from_str(&exported).unwrap();
+    assert_eq!(state(&replay.play().unwrap()), state(&sim));
+}
+
+#[test]
+fn quiet_frames_take_no_room() {
+    let mut sim = seeded();
+    for _ in 0..600 {
+        sim.step(DT);
+    }
+    let replay = sim.replay();
+    assert_eq!(replay.ticks(), 600);
+    assert!(matches!(replay.actions[..], [Action::SetScene { .. }, Action::Ticks { count: 600 }]), "{:?}", replay.actions);
+}
+
+#[test]
+fn player_keeps_the_pace_of_the_clock() {
+    let mut sim = seeded();
+    sim.shoot(&shot(0, -3.0, 2.8, -0.1, 14.0, banana(false, 5.0)));
+    for _ in 0..90 {
+        sim.step(DT);
+    }
+
+    let mut player = ReplayPlayer::new(sim.replay().clone());
+    let mut replayed = player.simulation();
+    for _ in 0..30 {
+        player.step(&mut replayed, DT).unwrap();
+    }
+    assert_eq!(replayed.replay().ticks(), 30);
+    assert_eq!(replayed.bananas().len(), 1);
+    assert!(!player.is_finished());
+
+    for _ in 0..60 {
+        player.step(&mut replayed, DT).unwrap();
+    }
+    assert!(player.is_finished());
+    assert_eq!(state(&replayed), state(&sim));
+}
+
+#[test]
+fn nothing_is_recorded_unless_asked() {
+    let mut sim = simulation(scene());
+    let saved = sim.snapshot();
+    for _ in 0..30 {
+        sim.step(DT);
+    }
+    sim.restore(&saved).unwrap();
+    assert!(sim.replay().actions.is_empty());
+    assert!(sim.replay().checksums.is_empty());
+    assert!(sim.replay().entity_checksums.is_empty());
+}
@@ -0,0 +1,253 @@
+//! Rollback peers over a connection with latency and lost messages still have to play the same match.
+
+mod common;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use common::{banana, game_config, recording, scene, shot, simulation_with, DT};
+use minimal::net::{Input, Message, Transport};
+use minimal::replay::Action;
+use minimal::rollback::{RollbackConfig, RollbackSession};
+use minimal::{GameEvent, Simulation};
+use serde_json::json;
+
+/// Messages in flight: when they arrive, in frames since the start, and what they say.
+type Wire = Rc<RefCell<VecDeque<(u64, String)>>>;
+
+/// Delivers messages `latency` frames after they were sent and drops about one in `loss` inputs, acks and checksums.
+/// Both ends share one clock, the test moves it on.
+struct FakeNet {
+    outbox: Wire,
+    inbox: Wire,
+    clock: Rc<RefCell<u64>>,
+    latency: u64,
+    loss: Option<u64>,
+    /// decides which messages get lost, the same ones on every run
+    dice: u64,
+}
+
+impl FakeNet {
+    fn pair(latency: u64, loss: Option<u64>) -> (FakeNet, FakeNet, Rc<RefCell<u64>>) {
+        let (a, b): (Wire, Wire) = Default::default();
+        let clock = Rc::new(RefCell::new(0));
+        let end = |outbox: &Wire, inbox: &Wire, dice| FakeNet {
+            outbox: outbox.clone(), inbox: inbox.clone(), clock: clock.clone(), latency, loss, dice,
+        };
+        (end(&a, &b, 1), end(&b, &a, 2), clock.clone())
+    }
+
+    fn roll(&mut self) -> u64 {
+        // xorshift
+        self.dice ^= self.dice << 13;
+        self.dice ^= self.dice >> 7;
+        self.dice ^= self.dice << 17;
+        self.dice
+    }
+}
+
+impl Transport for FakeNet {
+    fn send(&mut self, message: &Message) {
+        let reliable = matches!(message, Message::Join { .. } | Message::Resync { .. });
+        if let Some(loss) = self.loss {
+            if self.roll().is_multiple_of(loss) && !reliable {
+                return;
+            }
+        }
+        let arrival = *self.clock.borrow() + self.latency;
+        self.outbox.borrow_mut().push_back((arrival, serde_json::to_string(message).unwrap()));
+    }
+
+    fn receive(&mut self) -> Vec<Message> {
+        let now = *self.clock.borrow();
+        let mut inbox = self.inbox.borrow_mut();
+        let mut arrived = Vec::new();
+        while inbox.front().is_some_and(|(arrival, _)| *arrival <= now) {
+            let (_, text) = inbox.pop_front().unwrap();
+            arrived.push(serde_json::from_str(&text).unwrap());
+        }
+        arrived
+    }
+}
+
+struct Peer {
+    sim: Simulation,
+    session: RollbackSession<FakeNet>,
+    /// everything `poll_events` returned, like the frontend sees it
+    events: Vec<GameEvent>,
+}
+
+impl Peer {
+    fn new(sim: Simulation, session: RollbackSession<FakeNet>) -> Self {
+        Peer { sim, session, events: Vec::new() }
+    }
+
+    fn step(&mut self) {
+        self.step_by(DT);
+    }
+
+    fn step_by(&mut self, dt: f64) {
+        self.session.step(&mut self.sim, dt).unwrap();
+        self.events.extend(self.sim.poll_events());
+    }
+
+    fn shots_fired_by(&self, gorilla: usize) -> usize {
+        self.events.iter().filter(|event| matches!(event, GameEvent::ShotFired { gorilla: by, .. } if *by == gorilla)).count()
+    }
+}
+
+struct Match {
+    host: Peer,
+    guest: Peer,
+    clock: Rc<RefCell<u64>>,
+}
+
+impl Match {
+    fn new(latency: u64, loss: Option<u64>) -> Self {
+        Match::with_config(latency, loss, json!({ "input_delay": 1, "max_rollback": 8, "checksum_interval": 5 }))
+    }
+
+    fn with_config(latency: u64, loss: Option<u64>, config: serde_json::Value) -> Self {
+        let config: RollbackConfig = serde_json::from_value(config).unwrap();
+        let (a, b, clock) = FakeNet::pair(latency, loss);
+        Match {
+            host: Peer::new(simulation_with(recording(0), scene()), RollbackSession::new(a, 0, &config).unwrap()),
+            guest: Peer::new(Simulation::new(game_config()), RollbackSession::new(b, 1, &config).unwrap()),
+            clock,
+        }
+    }
+
+    fn run(&mut self, frames: u64) {
+        for _ in 0..frames {
+            self.host.step();
+            self.guest.step();
+            *self.clock.borrow_mut() += 1;
+        }
+    }
+
+    /// Both throw at `frame`, then the match plays on until `frames` and both sides settle.
+    fn play(&mut self, frame: u64, frames: u64) {
+        self.run(frame);
+        self.host.session.input(Input::Shoot { shot: shot(0, -3.0, 2.8, -0.1, 14.0, banana(false, 5.0)) });
+        self.guest.session.input(Input::Shoot { shot: shot(1, 3.0, 2.8, -3.0, 14.0, banana(false, 5.0)) });
+        self.run(frames - frame);
+    }
+
+    /// Lets the peer that started later catch up, then runs until both heard everything from each other.
+    fn settle(&mut self) {
+        for _ in 0..100 {
+            let (host, guest) = (self.host.session.status().frame, self.guest.session.status().frame);
+            self.host.step_by(if host < guest { DT } else { 0.0 });
+            self.guest.step_by(if guest < host { DT } else { 0.0 });
+            *self.clock.borrow_mut() += 1;
+        }
+    }
+}
+
+#[test]
+fn late_inputs_are_rolled_back_into_place() {
+    let mut remote = Match::new(4, None);
+    remote.play(10, 80);
+    remote.settle();
+
+    let (host, guest) = (remote.host.session.status(), remote.guest.session.status());
+    assert!(host.rollbacks > 0);
+    assert_eq!(host.desync, None);
+    assert_eq!(guest.desync, None);
+    assert_eq!(host.frame, guest.frame);
+    assert_eq!(remote.host.sim.checksum(), remote.guest.sim.checksum());
+}
+
+#[test]
+fn rolled_back_frames_dont_repeat_events() {
+    let mut remote = Match::new(4, None);
+    remote.play(10, 80);
+    remote.settle();
+
+    assert!(remote.host.session.status().rollbacks > 0);
+    for peer in [&remote.host, &remote.guest].iter() {
+        assert_eq!(peer.shots_fired_by(0), 1, "{:?}", peer.events);
+        assert_eq!(peer.shots_fired_by(1), 1, "{:?}", peer.events);
+    }
+    assert_eq!(remote.host.events, remote.guest.events);
+}
+
+#[test]
+fn lost_messages_are_sent_again() {
+    let mut lossy = Match::new(3, Some(4));
+    lossy.play(10, 120);
+    lossy.settle();
+
+    let (host, guest) = (lossy.host.session.status(), lossy.guest.session.status());
+    assert_eq!(host.desync, None);
+    assert_eq!(guest.desync, None);
+    assert_eq!(host.frame, guest.frame);
+    // nothing left to guess
+    assert!(host.confirmed >= host.frame.unwrap());
+    assert!(guest.confirmed >= guest.frame.unwrap());
+    assert_eq!(lossy.host.sim.checksum(), lossy.guest.sim.checksum());
+    assert_eq!(lossy.host.sim.bananas().len(), lossy.guest.sim.bananas().len());
+}
+
+#[test]
+fn no_peer_runs_further_ahead_than_it_can_roll_back() {
+    let mut game = Match::new(0, None);
+    game.run(5);
+    for _ in 0..30 {
+        game.host.step();
+        *game.clock.borrow_mut() += 1;
+    }
+    let status = game.host.session.status();
+    assert!(status.waiting);
+    assert_eq!(status.frame, Some(status.confirmed + 8));
+
+    // from here on it keeps pace with the guest
+    game.run(10);
+    let now = game.host.session.status();
+    assert!(now.confirmed >= status.confirmed + 9);
+    assert_eq!(now.frame, Some(now.confirmed + 8));
+}
+
+#[test]
+fn desync_is_caught_and_resynced() {
+    let mut game = Match::new(2, None);
+    game.run(10);
+    // behind the session's back
+    game.guest.sim.move_gorilla(1, serde_json::from_value(json!({ "x": 0.0, "y": -0.5 })).unwrap()).unwrap();
+    game.run(30);
+    game.settle();
+
+    assert!(game.host.session.status().desync.is_some());
+    assert_eq!(game.host.session.status().frame, game.guest.session.status().frame);
+    assert_eq!(game.host.sim.checksum(), game.guest.sim.checksum());
+}
+
+#[test]
+fn checkpoints_every_few_frames() {
+    let config = json!({ "input_delay": 1, "max_rollback": 8, "checksum_interval": 5, "checkpoint_interval": 6 });
+    let mut game = Match::with_config(4, Some(5), config);
+    game.play(10, 90);
+    game.settle();
+
+    let (host, guest) = (game.host.session.status(), game.guest.session.status());
+    assert!(host.rollbacks > 0);
+    assert_eq!(host.desync, None);
+    assert_eq!(guest.desync, None);
+    assert_eq!(game.host.sim.checksum(), game.guest.sim.checksum());
+
+    let replay = game.host.sim.replay();
+    let reloads = replay.actions.iter().filter(|action| matches!(action, Action::Reload)).count() as u64;
+    assert!(reloads < host.frame.unwrap() / 3, "{} reloads in {} frames", reloads, host.frame.unwrap());
+    assert_eq!(replay.verify().unwrap(), None);
+}
+
+#[test]
+fn rollback_matches_replay() {
+    let mut game = Match::new(4, None);
+    game.play(10, 60);
+    game.settle();
+    let replay = game.host.sim.replay();
+    assert_eq!(replay.verify().unwrap(), None);
+    assert_eq!(replay.play().unwrap().checksum(), game.host.sim.checksum());
+}
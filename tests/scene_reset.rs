@@ -0,0 +1,75 @@
+//! Reloading a scene must leave nothing of the old one behind.
+
+mod common;
+
+use common::{banana, scene, shot, simulation, DT};
+use minimal::SceneConfig;
+use serde_json::json;
+
+fn collider_count(sim: &minimal::Simulation) -> usize {
+    sim.world().colliders().count()
+}
+
+#[test]
+fn set_scene_twice_replaces_the_scene() {
+    let mut sim = simulation(scene());
+    let bricks = sim.bricks().len();
+    let colliders = collider_count(&sim);
+
+    let config: SceneConfig = serde_json::from_value(scene()).unwrap();
+    sim.set_scene(&config).unwrap();
+
+    assert_eq!(sim.bricks().len(), bricks);
+    assert_eq!(sim.gorillas().len(), 2);
+    assert_eq!(collider_count(&sim), colliders);
+}
+
+#[test]
+fn switching_levels_drops_the_old_buildings() {
+    let mut sim = simulation(scene());
+
+    let mut small = scene();
+    small["buildings"] = json!([{ "x": 0.0, "w": 1, "h": 1, "fill_style": "white" }]);
+    let small: SceneConfig = serde_json::from_value(small).unwrap();
+    sim.set_scene(&small).unwrap();
+
+    // one row of a single brick plus the half brick, and the roof
+    assert_eq!(sim.bricks().len(), 3);
+    // ground, bricks and gorillas
+    assert_eq!(collider_count(&sim), 1 + 3 + 2);
+}
+
+#[test]
+fn reset_rebuilds_destroyed_bricks_and_removes_bananas() {
+    let mut sim = simulation(scene());
+    let bricks = sim.bricks().len();
+    let colliders = collider_count(&sim);
+
+    sim.shoot(&shot(0, -3.0, 3.5, 0.0, 14.0, banana(true, 10.0)));
+    for _ in 0..60 {
+        sim.step(DT);
+    }
+    assert!(sim.bricks().len() < bricks);
+
+    sim.shoot(&shot(1, 0.0, -5.0, 0.0, 1.0, banana(false, 10.0)));
+    assert!(!sim.bananas().is_empty());
+
+    sim.reset().unwrap();
+    assert_eq!(sim.bricks().len(), bricks);
+    assert!(sim.bananas().is_empty());
+    assert_eq!(collider_count(&sim), colliders);
+    assert_eq!(sim.alpha(), 0.0);
+}
+
+#[test]
+fn clear_scene_leaves_an_empty_world() {
+    let mut sim = simulation(scene());
+    sim.clear_scene();
+
+    assert!(sim.bricks().is_empty());
+    assert!(sim.gorillas().is_empty());
+    assert_eq!(collider_count(&sim), 0);
+    assert!(sim.gorilla_pos(0).is_err());
+    // nothing left to simulate, but stepping must still work
+    sim.step(DT);
+}
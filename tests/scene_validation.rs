@@ -0,0 +1,128 @@
+//! `SceneConfig::validate` catches broken levels before they reach the physics world.
+
+mod common;
+
+use common::{game_config, scene};
+use minimal::validation::SceneProblem;
+use minimal::{GameError, SceneConfig, Simulation};
+use serde_json::{json, Value};
+
+fn problems(scene: Value) -> Vec<SceneProblem> {
+    let scene: SceneConfig = serde_json::from_value(scene).unwrap();
+    scene.validate().err().unwrap_or_default()
+}
+
+#[test]
+fn sane_scene_passes() {
+    assert_eq!(problems(scene()), vec![]);
+}
+
+#[test]
+fn adjacent_buildings_may_touch() {
+    // "valley" from index.js, neighbours share a few centimeters
+    let mut level = scene();
+    level["buildings"] = json!([
+        { "x": -7.0, "w": 7, "h": 40, "fill_style": "#04aaac" },
+        { "x": -4.3, "w": 5, "h": 25, "fill_style": "#ac0204" },
+        { "x": -2.0, "w": 5, "h": 15, "fill_style": "#acaaac" },
+        { "x":  2.0, "w": 5, "h": 15, "fill_style": "#aaac04" },
+        { "x":  4.3, "w": 5, "h": 25, "fill_style": "#04aaac" },
+        { "x":  7.0, "w": 7, "h": 40, "fill_style": "#aa04ac" },
+    ]);
+    level["player_a"]["x"] = json!(-7.0);
+    level["player_a"]["y"] = json!(-4.0);
+    level["player_b"]["x"] = json!(7.0);
+    level["player_b"]["y"] = json!(-4.0);
+
+    assert_eq!(problems(level), vec![]);
+}
+
+#[test]
+fn all_geometry_problems_are_reported_at_once() {
+    let mut level = scene();
+    level["buildings"] = json!([
+        { "x": -4.0, "w": 3, "h": 5, "fill_style": "red" },
+        { "x": -3.5, "w": 3, "h": 5, "fill_style": "red" },
+        { "x":  4.0, "w": 0, "h": 5, "fill_style": "red" },
+        { "x": 125.0, "w": 3, "h": 5, "fill_style": "red" },
+    ]);
+    // standing in the middle of the first building
+    level["player_a"]["y"] = json!(4.0);
+
+    let found = problems(level);
+    assert!(found.contains(&SceneProblem::EmptyBuilding { building: 2 }), "{:?}", found);
+    assert!(found.contains(&SceneProblem::BuildingOffGround { building: 3 }), "{:?}", found);
+    assert!(found.contains(&SceneProblem::PlayerInsideBuilding { player: "player_a".into(), building: 0 }), "{:?}", found);
+    assert!(found.iter().any(|p| matches!(p, SceneProblem::BuildingsOverlap { a: 0, b: 1, .. })), "{:?}", found);
+}
+
+#[test]
+fn degenerate_sizes() {
+    let mut level = scene();
+    level["box_radx"] = json!(-0.2);
+    level["player_b"]["rady"] = json!(0.0);
+    level["buildings"][0]["strength"] = json!(0.0);
+    level["buildings"][1]["strength"] = json!(-1.0);
+
+    let found = problems(level);
+    assert_eq!(found, vec![
+        SceneProblem::NotPositive { field: "box_radx".into(), value: -0.2 },
+        SceneProblem::NotPositive { field: "player_b.rady".into(), value: 0.0 },
+        SceneProblem::NotPositive { field: "buildings[1].strength".into(), value: -1.0 },
+    ]);
+}
+
+#[test]
+fn geometry_is_checked_around_broken_numbers() {
+    let mut level = scene();
+    // standing in the middle of the first building
+    level["player_a"]["y"] = json!(4.0);
+    level["player_b"]["radx"] = json!(-0.2);
+    level["buildings"][1]["strength"] = json!(-1.0);
+
+    let found = problems(level.clone());
+    assert!(found.contains(&SceneProblem::PlayerInsideBuilding { player: "player_a".into(), building: 0 }), "{:?}", found);
+    // nothing about where player_b stands, its size is broken
+    assert_eq!(found.len(), 3, "{:?}", found);
+
+    // bricks of a broken size would be anywhere, but empty buildings are empty either way
+    level["box_radx"] = json!(-0.2);
+    level["buildings"][1]["w"] = json!(0);
+    level["buildings"].as_array_mut().unwrap().push(json!({ "x": -3.5, "w": 3, "h": 5, "fill_style": "red" }));
+    let found = problems(level);
+    assert!(found.contains(&SceneProblem::EmptyBuilding { building: 1 }), "{:?}", found);
+    assert!(!found.iter().any(|p| matches!(p, SceneProblem::BuildingsOverlap { .. } | SceneProblem::PlayerInsideBuilding { .. })), "{:?}", found);
+}
+
+#[test]
+fn debris_counts_are_bounded() {
+    let mut level = scene();
+    level["debris"] = json!({ "split": 1000, "budget": 1_000_000 });
+    assert_eq!(problems(level), vec![
+        SceneProblem::TooLarge { field: "debris.split".into(), value: 1000, max: 8 },
+        SceneProblem::TooLarge { field: "debris.budget".into(), value: 1_000_000, max: 1_000 },
+    ]);
+
+    let mut level = scene();
+    level["debris"] = json!({ "split": 8, "budget": 0 });
+    assert_eq!(problems(level), vec![]);
+}
+
+#[test]
+fn set_scene_rejects_invalid_scenes() {
+    let mut level = scene();
+    level["name"] = json!("broken");
+    level["player_b"]["y"] = json!(9.0);
+    let level: SceneConfig = serde_json::from_value(level).unwrap();
+
+    let mut sim = Simulation::new(game_config());
+    match sim.set_scene(&level) {
+        Err(GameError::InvalidScene { level, problems }) => {
+            assert_eq!(level.as_deref(), Some("broken"));
+            assert_eq!(problems, vec![SceneProblem::PlayerInsideGround { player: "player_b".into() }]);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(sim.bricks().is_empty());
+    assert!(sim.gorillas().is_empty());
+}
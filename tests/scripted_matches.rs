@@ -0,0 +1,113 @@
+//! Headless matches: load a scene, fire scripted shots at given frames and check the outcome.
+
+mod common;
+
+use common::{banana, scene, shot, simulation, DT};
+use minimal::{Shot, Simulation};
+use serde_json::Value;
+
+/// A straight throw from the roof of the left building into the facade of the right one.
+fn throw_at_right_building(config: Value) -> Shot {
+    shot(0, -3.0, 3.5, 0.0, 14.0, config)
+}
+
+struct ScriptedMatch {
+    sim: Simulation,
+    shots: Vec<(usize, Shot)>,
+    frame: usize,
+}
+
+impl ScriptedMatch {
+    fn new(scene: Value) -> Self {
+        ScriptedMatch { sim: simulation(scene), shots: Vec::new(), frame: 0 }
+    }
+
+    /// Fires `shot` right before stepping frame `frame`.
+    fn at(mut self, frame: usize, shot: Shot) -> Self {
+        self.shots.push((frame, shot));
+        self
+    }
+
+    fn run(&mut self, frames: usize) -> &Simulation {
+        for _ in 0..frames {
+            let frame = self.frame;
+            for (_, shot) in self.shots.iter().filter(|(at, _)| *at == frame) {
+                self.sim.shoot(shot);
+            }
+            self.sim.step(DT);
+            self.frame += 1;
+        }
+        &self.sim
+    }
+}
+
+/// every row has `w` bricks plus a half brick at alternating ends, topped by one roof brick
+fn bricks_in_building(w: usize, h: usize) -> usize {
+    h * (w + 1) + 1
+}
+
+#[test]
+fn set_scene_builds_bricks_and_gorillas() {
+    let game = ScriptedMatch::new(scene());
+    assert_eq!(game.sim.bricks().len(), 2 * bricks_in_building(3, 5));
+    assert_eq!(game.sim.gorillas().len(), 2);
+    assert!(game.sim.bananas().is_empty());
+}
+
+#[test]
+fn gorillas_settle_on_their_roofs() {
+    let mut game = ScriptedMatch::new(scene());
+    let sim = game.run(120);
+
+    // ground surface is at 4.5, five rows of 0.2 each and the roof brick put the roof top near 3.4
+    for (index, x) in [(0, -4.0), (1, 4.0)].iter() {
+        let pos = sim.gorilla_pos(*index).unwrap();
+        assert!((pos.x - x).abs() < 0.1, "gorilla {} drifted to {:?}", index, pos);
+        assert!(pos.y > 2.8 && pos.y < 3.4, "gorilla {} is not standing on the roof: {:?}", index, pos);
+    }
+
+    // nothing was thrown, nothing may break
+    assert_eq!(sim.bricks().len(), 2 * bricks_in_building(3, 5));
+}
+
+#[test]
+fn banana_expires_after_ttl_in_free_flight() {
+    // straight up, nothing to hit
+    let up = shot(0, 0.0, -5.0, -std::f64::consts::FRAC_PI_2, 5.0, banana(false, 0.5));
+    let mut game = ScriptedMatch::new(scene()).at(0, up);
+
+    assert_eq!(game.run(1).bananas().len(), 1);
+    assert_eq!(game.run(20).bananas().len(), 1);
+    assert!(game.run(20).bananas().is_empty());
+}
+
+#[test]
+fn cooldown_rejects_rapid_fire() {
+    let config = banana(false, 10.0);
+    let mut game = ScriptedMatch::new(scene())
+        .at(0, shot(0, -4.0, -2.0, -1.0, 5.0, config.clone()))
+        .at(5, shot(0, -4.0, -2.0, -1.0, 5.0, config.clone()))
+        .at(30, shot(0, -4.0, -2.0, -1.0, 5.0, config.clone()));
+
+    assert_eq!(game.run(10).bananas().len(), 1, "second shot should be rejected, cost is 0.3s");
+    assert_eq!(game.run(30).bananas().len(), 2);
+}
+
+#[test]
+fn plain_banana_bounces_off_bricks() {
+    let mut game = ScriptedMatch::new(scene()).at(10, throw_at_right_building(banana(false, 10.0)));
+    let sim = game.run(120);
+
+    assert_eq!(sim.bricks().len(), 2 * bricks_in_building(3, 5));
+    // hitting a brick caps the remaining ttl at the default stamina
+    assert!(sim.bananas().is_empty());
+}
+
+#[test]
+fn explosive_banana_destroys_bricks() {
+    let mut game = ScriptedMatch::new(scene()).at(10, throw_at_right_building(banana(true, 10.0)));
+    let sim = game.run(120);
+
+    assert!(sim.bricks().len() < 2 * bricks_in_building(3, 5));
+    assert!(sim.bananas().is_empty());
+}
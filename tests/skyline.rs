@@ -0,0 +1,86 @@
+//! Procedurally generated cities.
+
+mod common;
+
+use common::{game_config, DT};
+use minimal::shapes::{generate_skyline, SkylineConfig};
+use minimal::{GameError, LevelRegistry, SceneConfig, Simulation};
+
+fn template() -> SceneConfig {
+    LevelRegistry::builtin().unwrap().get("stairs").unwrap().clone()
+}
+
+fn seeded(seed: u64) -> SkylineConfig {
+    SkylineConfig { seed, ..SkylineConfig::default() }
+}
+
+fn json(scene: &SceneConfig) -> serde_json::Value {
+    serde_json::to_value(scene).unwrap()
+}
+
+#[test]
+fn same_seed_same_city() {
+    let a = generate_skyline(&seeded(42), &template()).unwrap();
+    let b = generate_skyline(&seeded(42), &template()).unwrap();
+    let c = generate_skyline(&seeded(43), &template()).unwrap();
+
+    assert_eq!(json(&a), json(&b));
+    assert_ne!(json(&a)["buildings"], json(&c)["buildings"]);
+    assert_eq!(json(&a)["name"], "skyline-42");
+}
+
+#[test]
+fn generated_cities_are_valid() {
+    let template = template();
+    for seed in 0..200 {
+        let params = SkylineConfig { seed, buildings: 2 + seed as usize % 10, gap: (seed % 3) as f64 * 0.2, ..SkylineConfig::default() };
+        let scene = generate_skyline(&params, &template).unwrap_or_else(|err| panic!("seed {}: {}", seed, err));
+        assert!(scene.validate().is_ok());
+
+        let buildings = json(&scene)["buildings"].as_array().unwrap().clone();
+        assert_eq!(buildings.len(), params.buildings);
+        for building in &buildings {
+            let (w, h) = (building["w"].as_u64().unwrap() as usize, building["h"].as_u64().unwrap() as usize);
+            assert!(params.min_width <= w && w <= params.max_width);
+            assert!(params.min_height <= h && h <= params.max_height);
+        }
+    }
+}
+
+#[test]
+fn players_land_on_their_roofs() {
+    let scene = generate_skyline(&seeded(7), &template()).unwrap();
+    let spawn = json(&scene);
+    let mut sim = Simulation::new(game_config());
+    sim.set_scene(&scene).unwrap();
+    for _ in 0..120 {
+        sim.step(DT);
+    }
+
+    for (index, player) in ["player_a", "player_b"].iter().enumerate() {
+        let pos = sim.gorilla_pos(index).unwrap();
+        let (x, y) = (spawn[player]["x"].as_f64().unwrap(), spawn[player]["y"].as_f64().unwrap());
+        // tall buildings sway a little while they settle, falling off a roof is a lot more than that
+        assert!((pos.x - x).abs() < 0.3, "{} drifted from {} to {:?}", player, x, pos);
+        assert!(pos.y > y && pos.y - y < 0.3, "{} fell from {} to {:?}", player, y, pos);
+    }
+}
+
+#[test]
+fn rejects_impossible_parameters() {
+    let template = template();
+    let cases = vec![
+        ("buildings", SkylineConfig { buildings: 1, ..SkylineConfig::default() }),
+        ("min_height", SkylineConfig { min_height: 20, max_height: 10, ..SkylineConfig::default() }),
+        ("min_width", SkylineConfig { min_width: 0, ..SkylineConfig::default() }),
+        ("gap", SkylineConfig { gap: -1.0, ..SkylineConfig::default() }),
+        ("palette", SkylineConfig { palette: vec![], ..SkylineConfig::default() }),
+        ("buildings", SkylineConfig { buildings: 1000, ..SkylineConfig::default() }),
+    ];
+    for (field, params) in cases {
+        match generate_skyline(&params, &template) {
+            Err(GameError::Config(err)) => assert_eq!(err.field.as_deref(), Some(field)),
+            other => panic!("{} should be rejected, got {:?}", field, other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! Snapshots put a simulation back the way it was, in the same or in a fresh one.
+
+mod common;
+
+use common::{banana, game_config, scene, shot, simulation, DT};
+use minimal::{GameEvent, MatchState, Simulation, Snapshot};
+use serde_json::Value;
+
+fn settled() -> Simulation {
+    let mut sim = simulation(scene());
+    for _ in 0..60 {
+        sim.step(DT);
+    }
+    sim
+}
+
+/// Restored rotations go through an angle and back, allow for the rounding.
+fn assert_close(a: &Value, b: &Value, path: &str) {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => {
+            let (x, y) = (x.as_f64().unwrap(), y.as_f64().unwrap());
+            assert!((x - y).abs() < 1e-9, "{}: {} != {}", path, x, y);
+        }
+        (Value::Array(xs), Value::Array(ys)) => {
+            assert_eq!(xs.len(), ys.len(), "{}", path);
+            for (i, (x, y)) in xs.iter().zip(ys).enumerate() {
+                assert_close(x, y, &format!("{}[{}]", path, i));
+            }
+        }
+        (Value::Object(xs), Value::Object(ys)) => {
+            assert_eq!(xs.len(), ys.len(), "{}", path);
+            for (key, x) in xs {
+                assert_close(x, &ys[key], &format!("{}.{}", path, key));
+            }
+        }
+        _ => assert_eq!(a, b, "{}", path),
+    }
+}
+
+fn hits(events: &[GameEvent]) -> Vec<String> {
+    events.iter().filter_map(|event| match event {
+        GameEvent::BananaHitBrick { banana, brick, .. } => Some(format!("{:?} hit {:?}", banana, brick)),
+        GameEvent::BrickDestroyed { brick, .. } => Some(format!("{:?} destroyed", brick)),
+        _ => None,
+    }).collect()
+}
+
+#[test]
+fn restore_rebuilds_every_body() {
+    let mut sim = settled();
+    sim.shoot(&shot(0, -3.0, 2.8, -0.1, 14.0, banana(false, 5.0)));
+    for _ in 0..10 {
+        sim.step(DT);
+    }
+    let before = serde_json::to_value(sim.snapshot()).unwrap();
+
+    let mut restored = Simulation::new(game_config());
+    restored.restore(&sim.snapshot()).unwrap();
+    let after = serde_json::to_value(restored.snapshot()).unwrap();
+
+    assert_close(&before, &after, "snapshot");
+    assert_eq!(restored.debug_counts().bodies, sim.debug_counts().bodies);
+    assert_eq!(restored.bananas().len(), 1);
+}
+
+#[test]
+fn restored_game_plays_on_with_the_same_ids() {
+    let mut sim = settled();
+    sim.shoot(&shot(0, -3.0, 2.8, -0.1, 14.0, banana(false, 5.0)));
+    sim.step(DT);
+    let snapshot = sim.snapshot();
+
+    let mut restored = Simulation::new(game_config());
+    restored.restore(&snapshot).unwrap();
+    sim.poll_events();
+    for _ in 0..60 {
+        sim.step(DT);
+        restored.step(DT);
+    }
+
+    let original = hits(&sim.poll_events());
+    assert!(!original.is_empty(), "the banana should have hit the building");
+    assert_eq!(hits(&restored.poll_events()), original);
+}
+
+#[test]
+fn save_game_round_trips_through_json() {
+    let mut sim = settled();
+    sim.start_match().unwrap();
+    for _ in 0..200 {
+        sim.step(DT);
+    }
+    assert_eq!(sim.match_status().state, MatchState::Playing);
+    sim.shoot(&shot(1, 3.0, 2.8, -3.0, 5.0, banana(false, 5.0)));
+    sim.step(DT);
+
+    let saved = serde_json::to_string(&sim.snapshot()).unwrap();
+    let snapshot: Snapshot = serde_json::from_str(&saved).unwrap();
+    let mut loaded = Simulation::new(game_config());
+    loaded.restore(&snapshot).unwrap();
+
+    assert_eq!(loaded.match_status(), sim.match_status());
+    assert_eq!(loaded.seed(), sim.seed());
+    assert_eq!(loaded.wind(), sim.wind());
+    let cooldown = |sim: &Simulation| sim.gorillas()[1].time_to_next_shot;
+    assert_eq!(cooldown(&loaded), cooldown(&sim));
+    assert!(cooldown(&loaded) > 0.0);
+
+    // the same thing rejected for the same reason
+    loaded.shoot(&shot(1, 3.0, 2.8, -3.0, 5.0, banana(false, 5.0)));
+    assert!(matches!(loaded.poll_events()[..], [GameEvent::ShotRejectedCooldown { gorilla: 1, .. }]));
+}
+
+#[test]
+fn snapshot_with_stale_ids_is_rejected() {
+    let mut sim = settled();
+    let counts = sim.debug_counts();
+
+    let mut snapshot = sim.snapshot();
+    snapshot.bricks.items[0].id.generation += 1;
+
+    assert!(sim.restore(&snapshot).is_err());
+    assert_eq!(sim.debug_counts(), counts);
+}
@@ -0,0 +1,74 @@
+//! `Simulation::step` only advances physics in whole ticks and carries the rest over.
+
+mod common;
+
+use common::{banana, scene, shot, simulation, simulation_with, DT};
+use serde_json::json;
+
+fn assert_close(a: f64, b: f64) {
+    assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+}
+
+#[test]
+fn leftover_time_is_carried_over() {
+    let mut sim = simulation(scene());
+    sim.shoot(&shot(0, 0.0, -5.0, 0.0, 1.0, banana(false, 10.0)));
+
+    sim.step(DT * 0.5);
+    assert_close(sim.alpha(), 0.5);
+    assert_close(sim.bananas()[0].ttl, 10.0);
+
+    sim.step(DT * 0.75);
+    assert_close(sim.alpha(), 0.25);
+    assert_close(sim.bananas()[0].ttl, 10.0 - DT);
+}
+
+#[test]
+fn frame_rate_does_not_change_the_outcome() {
+    let mut at_60hz = simulation(scene());
+    let mut at_120hz = simulation(scene());
+
+    for _ in 0..60 {
+        at_60hz.step(DT);
+    }
+    for _ in 0..120 {
+        at_120hz.step(DT * 0.5);
+    }
+
+    for index in 0..2 {
+        let (a, b) = (at_60hz.gorilla_pos(index).unwrap(), at_120hz.gorilla_pos(index).unwrap());
+        assert_eq!((a.x, a.y), (b.x, b.y));
+    }
+}
+
+#[test]
+fn substeps_are_capped() {
+    let config = serde_json::from_value(json!({ "max_substeps": 3 })).unwrap();
+    let mut sim = simulation_with(config, scene());
+    sim.shoot(&shot(0, 0.0, -5.0, 0.0, 1.0, banana(false, 10.0)));
+
+    // a long hiccup, e.g. the tab was in the background
+    sim.step(2.0);
+    assert_close(sim.bananas()[0].ttl, 10.0 - 3.0 * DT);
+    assert!(sim.alpha() < 1.0);
+}
+
+#[test]
+fn broken_frame_times_are_ignored() {
+    let mut sim = simulation(scene());
+    sim.shoot(&shot(0, 0.0, -5.0, 0.0, 1.0, banana(false, 10.0)));
+    sim.step(DT * 0.5);
+
+    for &dt in &[f64::NAN, f64::INFINITY, f64::NEG_INFINITY, -DT] {
+        sim.step(dt);
+        assert_close(sim.alpha(), 0.5);
+    }
+    assert_close(sim.bananas()[0].ttl, 10.0);
+
+    // as capped as any other long hiccup, and the leftover is still good to carry over
+    sim.step(f64::MAX);
+    assert_close(sim.bananas()[0].ttl, 10.0 - 5.0 * DT);
+    assert_close(sim.alpha(), 0.5);
+    sim.step(DT * 0.5);
+    assert_close(sim.bananas()[0].ttl, 10.0 - 6.0 * DT);
+}
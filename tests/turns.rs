@@ -0,0 +1,97 @@
+//! The classic turn-based mode.
+
+mod common;
+
+use common::{banana, scene, shot, simulation_with, DT};
+use minimal::match_state::{Turn, TurnPhase};
+use minimal::{MatchState, Simulation};
+use serde_json::json;
+
+fn turn_based(turn_time: Option<f64>) -> Simulation {
+    turn_based_with(json!({ "mode": "TurnBased", "countdown": 0.1, "turn_time": turn_time }))
+}
+
+fn turn_based_with(rules: serde_json::Value) -> Simulation {
+    let config = serde_json::from_value(json!({ "rules": rules })).unwrap();
+    let mut sim = simulation_with(config, scene());
+    sim.start_match().unwrap();
+    run(&mut sim, 0.2);
+    assert_eq!(sim.game_match().state(), &MatchState::Playing);
+    sim
+}
+
+fn run(sim: &mut Simulation, seconds: f64) {
+    for _ in 0..(seconds / DT).round() as usize {
+        sim.step(DT);
+    }
+}
+
+fn active(sim: &Simulation) -> Option<usize> {
+    match sim.game_match().turn() {
+        Some(Turn { player, phase: TurnPhase::Aiming { .. } }) => Some(*player),
+        _ => None,
+    }
+}
+
+/// Straight up and out of the way, expires after half a second.
+fn throw(player: usize) -> minimal::Shot {
+    let x = if player == 0 { -4.0 } else { 4.0 };
+    shot(player, x, 1.0, -std::f64::consts::FRAC_PI_2, 2.0, banana(false, 0.5))
+}
+
+#[test]
+fn only_the_active_player_throws_once() {
+    let mut sim = turn_based(None);
+    assert_eq!(active(&sim), Some(0));
+
+    sim.shoot(&throw(1));
+    assert!(sim.bananas().is_empty(), "not player 1's turn");
+
+    sim.shoot(&throw(0));
+    assert_eq!(sim.bananas().len(), 1);
+    assert!(matches!(sim.game_match().turn().unwrap().phase, TurnPhase::Resolving { .. }));
+
+    run(&mut sim, 0.4);
+    sim.shoot(&throw(0));
+    assert_eq!(sim.bananas().len(), 1, "one throw per turn");
+}
+
+#[test]
+fn turn_passes_once_the_world_has_settled() {
+    let mut sim = turn_based(None);
+    sim.shoot(&throw(0));
+
+    run(&mut sim, 0.3);
+    assert_eq!(active(&sim), None, "banana still in the air");
+
+    run(&mut sim, 1.0);
+    assert!(sim.is_settled());
+    assert_eq!(active(&sim), Some(1));
+
+    sim.shoot(&throw(1));
+    assert_eq!(sim.bananas().len(), 1);
+}
+
+#[test]
+fn turn_timer_forfeits_the_turn() {
+    let mut sim = turn_based(Some(1.0));
+    run(&mut sim, 0.5);
+    assert_eq!(active(&sim), Some(0));
+    run(&mut sim, 0.6);
+    assert_eq!(active(&sim), Some(1));
+
+    sim.shoot(&throw(0));
+    assert!(sim.bananas().is_empty());
+}
+
+#[test]
+fn turn_passes_after_the_resolve_time_even_if_things_still_move() {
+    let mut sim = turn_based_with(json!({ "mode": "TurnBased", "countdown": 0.1, "resolve_time": 0.2 }));
+    sim.shoot(&throw(0));
+
+    run(&mut sim, 0.1);
+    assert_eq!(active(&sim), None);
+    run(&mut sim, 0.15);
+    assert!(!sim.is_settled(), "banana still in the air");
+    assert_eq!(active(&sim), Some(1));
+}
@@ -0,0 +1,79 @@
+//! Wind pushes bananas around.
+
+mod common;
+
+use common::{banana, scene, shot, simulation, DT};
+use minimal::{SceneConfig, Simulation};
+use serde_json::{json, Value};
+
+fn windy(wind: Value) -> Simulation {
+    let mut scene = scene();
+    scene["wind"] = wind;
+    simulation(scene)
+}
+
+/// Where a banana thrown straight up from x = 0 is after half a second.
+fn drift(sim: &mut Simulation) -> f64 {
+    sim.shoot(&shot(0, 0.0, -5.0, -std::f64::consts::FRAC_PI_2, 5.0, banana(false, 10.0)));
+    for _ in 0..30 {
+        sim.step(DT);
+    }
+    sim.pos_of(sim.bananas()[0].body).x
+}
+
+#[test]
+fn constant_wind_pushes_bananas() {
+    let mut calm = simulation(scene());
+    assert!(drift(&mut calm).abs() < 1e-9);
+
+    let mut sim = windy(json!({ "x": 4.0 }));
+    assert_eq!(sim.wind().x, 4.0);
+    // half of a * t²
+    let x = drift(&mut sim);
+    assert!((x - 0.5 * 4.0 * 0.25).abs() < 0.05, "drifted to {}", x);
+
+    let mut against = windy(json!({ "x": -4.0 }));
+    assert!((drift(&mut against) + x).abs() < 1e-9);
+}
+
+#[test]
+fn gusts_swing_around_the_base() {
+    let mut sim = windy(json!({ "mode": "Gusting", "x": 2.0, "gust": 0.5, "period": 1.0 }));
+    let mut seen = Vec::new();
+    for _ in 0..60 {
+        sim.step(DT);
+        seen.push(sim.wind().x);
+    }
+    let (low, high) = seen.iter().fold((f64::MAX, f64::MIN), |(lo, hi), &w| (lo.min(w), hi.max(w)));
+    assert!(low >= 1.0 - 1e-9 && high <= 3.0 + 1e-9, "{}..{}", low, high);
+    assert!(high - low > 1.5, "{}..{}", low, high);
+}
+
+#[test]
+fn random_wind_is_rolled_from_the_seed_every_round() {
+    let random = json!({ "mode": "Random", "x": 3.0 });
+    let mut a = windy(random.clone());
+    let mut b = windy(random);
+
+    let mut winds = Vec::new();
+    for _ in 0..5 {
+        assert_eq!(a.wind(), b.wind());
+        assert!(a.wind().x.abs() <= 3.0 && a.wind().y == 0.0);
+        winds.push(a.wind().x);
+        a.reset().unwrap();
+        b.reset().unwrap();
+    }
+    winds.dedup();
+    assert_eq!(winds.len(), 5);
+}
+
+#[test]
+fn broken_wind_is_rejected() {
+    let mut config = scene();
+    config["wind"] = json!({ "mode": "Gusting", "x": 2.0, "period": 0.0, "debris": -1.0 });
+    let config: SceneConfig = serde_json::from_value(config).unwrap();
+    let problems = config.validate().unwrap_err();
+    let fields: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+    assert_eq!(fields.len(), 2, "{:?}", fields);
+    assert!(fields[0].contains("wind.period") || fields[1].contains("wind.period"));
+}